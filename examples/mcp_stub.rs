@@ -0,0 +1,111 @@
+// examples/mcp_stub.rs
+//
+// A minimal MCP server over stdio for trying out the MCP client. Add it to
+// config.json:
+//
+//   "mcp_servers": {
+//     "stub": { "command": "cargo", "args": ["run", "-q", "--example", "mcp_stub"] }
+//   }
+//
+// It offers two tools (echo, add), one resource and one prompt. Tools are
+// listed one per page, and every tool call is preceded by a log notification,
+// so a client has to follow cursors and skip notifications.
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+
+fn tools() -> Vec<Value> {
+    vec![
+        json!({
+            "name": "echo",
+            "description": "Return the given text unchanged.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "text": { "type": "string" } },
+                "required": ["text"]
+            }
+        }),
+        json!({
+            "name": "add",
+            "description": "Add two numbers.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "a": { "type": "number" }, "b": { "type": "number" } },
+                "required": ["a", "b"]
+            }
+        }),
+    ]
+}
+
+fn handle(method: &str, params: &Value) -> Result<Value, (i64, String)> {
+    match method {
+        "initialize" => Ok(json!({
+            "protocolVersion": "2024-11-05",
+            "capabilities": { "tools": {}, "resources": {}, "prompts": {} },
+            "serverInfo": { "name": "mcp-stub", "version": "0.1.0" }
+        })),
+        "ping" => Ok(json!({})),
+        "tools/list" => {
+            let tools = tools();
+            let page: usize = params["cursor"].as_str().and_then(|c| c.parse().ok()).unwrap_or(0);
+            let mut result = json!({ "tools": tools.get(page).into_iter().collect::<Vec<_>>() });
+            if page + 1 < tools.len() {
+                result["nextCursor"] = json!((page + 1).to_string());
+            }
+            Ok(result)
+        }
+        "tools/call" => {
+            let arguments = &params["arguments"];
+            let text = match params["name"].as_str() {
+                Some("echo") => arguments["text"].as_str().unwrap_or_default().to_string(),
+                Some("add") => {
+                    let sum = arguments["a"].as_f64().unwrap_or(0.0) + arguments["b"].as_f64().unwrap_or(0.0);
+                    sum.to_string()
+                }
+                other => {
+                    return Ok(json!({
+                        "content": [{ "type": "text", "text": format!("unknown tool {:?}", other) }],
+                        "isError": true
+                    }))
+                }
+            };
+            Ok(json!({ "content": [{ "type": "text", "text": text }] }))
+        }
+        "resources/list" => Ok(json!({ "resources": [
+            { "uri": "stub://greeting", "name": "greeting", "description": "A friendly greeting" }
+        ]})),
+        "prompts/list" => Ok(json!({ "prompts": [
+            { "name": "hello", "description": "Say hello" }
+        ]})),
+        _ => Err((-32601, format!("Method not found: {}", method))),
+    }
+}
+
+fn main() -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    for line in stdin.lock().lines() {
+        let Ok(message) = serde_json::from_str::<Value>(&line?) else {
+            continue;
+        };
+        // Notifications have no id and get no reply
+        let Some(id) = message.get("id").cloned() else {
+            continue;
+        };
+        let method = message["method"].as_str().unwrap_or_default();
+        if method == "tools/call" {
+            let log = json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/message",
+                "params": { "level": "info", "data": format!("calling {}", message["params"]["name"]) }
+            });
+            writeln!(stdout, "{}", log)?;
+        }
+        let reply = match handle(method, &message["params"]) {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, text)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": text } }),
+        };
+        writeln!(stdout, "{}", reply)?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,228 @@
+// src/agent.rs
+use crate::config::Config;
+use crate::error::Result;
+use anyhow::Context;
+use serde::Deserialize;
+use std::io::{self, Write};
+use tracing::debug;
+
+// --- Constants ---
+const COMMAND_FENCE: &str = "```agent-command";
+
+// Characters the shell treats specially (chaining, substitution, redirection,
+// grouping, escapes); allow-listed commands containing any of them, or any
+// control character such as a newline, still need confirmation.
+const SHELL_METACHARACTERS: [char; 13] = [';', '&', '|', '`', '$', '>', '<', '(', ')', '{', '}', '\\', '!'];
+
+pub const AGENT_SYSTEM_PROMPT: &str = "You are working in the user's terminal. \
+You may ask to run one shell command at a time to inspect or change the local system. \
+To request a command, reply with a short explanation followed by exactly one block:\n\
+```agent-command\n{\"command\": \"<shell command>\"}\n```\n\
+The user approves or declines each command; you then receive its output (or the refusal) \
+and can request another. When you have enough information, reply with your final answer \
+and no agent-command block.";
+
+// --- Command Request ---
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommandRequest {
+    pub command: String,
+}
+
+// Extracts the command from the first agent-command block, if any
+pub fn parse_command_request(text: &str) -> Option<CommandRequest> {
+    let start = text.find(COMMAND_FENCE)? + COMMAND_FENCE.len();
+    let body = &text[start..];
+    let end = body.find("```")?;
+    match serde_json::from_str::<CommandRequest>(body[..end].trim()) {
+        Ok(request) if !request.command.trim().is_empty() => Some(request),
+        Ok(_) => None,
+        Err(e) => {
+            debug!("Malformed agent-command block: {}", e);
+            None
+        }
+    }
+}
+
+// --- Command Policy ---
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decision {
+    // Matches the deny list; never run
+    Deny(String),
+    // Matches the allow list; run without asking
+    Allow,
+    // Ask the user
+    Confirm,
+}
+
+pub fn check_command(config: &Config, command: &str) -> Decision {
+    let command = command.trim();
+    if let Some(pattern) = config.agent_deny_commands.iter().find(|p| command.contains(p.as_str())) {
+        return Decision::Deny(pattern.clone());
+    }
+    let chained = command.chars().any(|c| SHELL_METACHARACTERS.contains(&c) || c.is_control());
+    let allowed = config.agent_allow_commands.iter().any(|prefix| {
+        command == prefix || command.starts_with(&format!("{} ", prefix))
+    });
+    if allowed && !chained {
+        Decision::Allow
+    } else {
+        Decision::Confirm
+    }
+}
+
+// --- Approval ---
+#[derive(Debug, Clone, PartialEq)]
+pub enum Approval {
+    // Run this (possibly edited) command
+    Run(String),
+    // Not run; the message explains why and is sent back to the model
+    Refused(String),
+}
+
+// Applies the allow/deny lists and asks for y/n/edit confirmation otherwise
+pub fn approve_command(config: &Config, requested: &str) -> Result<Approval> {
+    let mut command = requested.trim().to_string();
+    println!("Model wants to run: {}", command);
+    match check_command(config, &command) {
+        Decision::Deny(pattern) => {
+            println!("Refused: matches deny pattern '{}'.", pattern);
+            println!("---");
+            return Ok(Approval::Refused(format!(
+                "The command `{}` was refused by policy (matches '{}'). Do not retry it.",
+                command, pattern
+            )));
+        }
+        Decision::Allow => println!("Auto-approved by allow list."),
+        Decision::Confirm => loop {
+            print!("Run it? [y]es / [n]o / [e]dit: ");
+            io::stdout().flush().context("Flush failed")?;
+            // End of input or a failed read counts as a refusal
+            let Some(answer) = read_answer() else {
+                println!();
+                println!("---");
+                return Ok(Approval::Refused(format!("The user declined to run `{}`.", command)));
+            };
+            match answer.trim().to_lowercase().as_str() {
+                "y" | "yes" => break,
+                "n" | "no" => {
+                    println!("---");
+                    return Ok(Approval::Refused(format!("The user declined to run `{}`.", command)));
+                }
+                "e" | "edit" => {
+                    print!("Command: ");
+                    io::stdout().flush().context("Flush failed")?;
+                    let Some(edited) = read_answer() else {
+                        println!();
+                        println!("---");
+                        return Ok(Approval::Refused(format!("The user declined to run `{}`.", command)));
+                    };
+                    if !edited.trim().is_empty() {
+                        command = edited.trim().to_string();
+                    }
+                    // Edited commands go through the deny list again
+                    if let Decision::Deny(pattern) = check_command(config, &command) {
+                        println!("Refused: matches deny pattern '{}'.", pattern);
+                        println!("---");
+                        return Ok(Approval::Refused(format!(
+                            "The command `{}` was refused by policy (matches '{}').",
+                            command, pattern
+                        )));
+                    }
+                    println!("Model wants to run: {}", command);
+                }
+                _ => println!("Please answer y, n or e."),
+            }
+        },
+    }
+    Ok(Approval::Run(command))
+}
+
+// One line from stdin, or None at end of input or on a read error
+fn read_answer() -> Option<String> {
+    let mut buf = String::new();
+    match io::stdin().read_line(&mut buf) {
+        Ok(0) => None,
+        Ok(_) => Some(buf),
+        Err(e) => {
+            debug!("Failed to read the answer: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(allow: &[&str], deny: &[&str]) -> Config {
+        Config {
+            agent_allow_commands: allow.iter().map(|s| s.to_string()).collect(),
+            agent_deny_commands: deny.iter().map(|s| s.to_string()).collect(),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn check_command_applies_deny_then_allow_lists() {
+        let config = config(&["ls", "git status"], &["rm -rf /", "sudo "]);
+        let cases = [
+            ("ls", Decision::Allow),
+            ("  ls -la src  ", Decision::Allow),
+            ("git status", Decision::Allow),
+            ("git status --short", Decision::Allow),
+            // A prefix must end at a word boundary
+            ("lsblk", Decision::Confirm),
+            ("git stash", Decision::Confirm),
+            ("cat README.md", Decision::Confirm),
+            // Deny patterns match anywhere and win over the allow list
+            ("ls; sudo reboot", Decision::Deny("sudo ".to_string())),
+            ("rm -rf /tmp/x", Decision::Deny("rm -rf /".to_string())),
+        ];
+        for (command, expected) in cases {
+            assert_eq!(check_command(&config, command), expected, "{:?}", command);
+        }
+    }
+
+    #[test]
+    fn check_command_confirms_allowed_commands_with_metacharacters() {
+        let config = config(&["ls", "echo"], &[]);
+        for command in [
+            "ls; cat /etc/passwd",
+            "ls && curl example.com",
+            "ls | sh",
+            "echo `id`",
+            "echo $(id)",
+            "echo $HOME",
+            "ls > out.txt",
+            "ls < in.txt",
+            "echo a\nb",
+            "ls\ncat secrets",
+            "ls\tsrc",
+            "echo !!",
+            "ls {a,b}",
+        ] {
+            assert_eq!(check_command(&config, command), Decision::Confirm, "{:?}", command);
+        }
+    }
+
+    #[test]
+    fn parse_command_request_reads_the_first_block() {
+        let text = "Let me look.\n```agent-command\n{\"command\": \"ls -la\"}\n```\n\
+                    ```agent-command\n{\"command\": \"pwd\"}\n```";
+        assert_eq!(parse_command_request(text).map(|r| r.command).as_deref(), Some("ls -la"));
+    }
+
+    #[test]
+    fn parse_command_request_ignores_missing_empty_and_malformed_blocks() {
+        for text in [
+            "No command here.",
+            "```bash\nls\n```",
+            "```agent-command\n{\"command\": \"ls\"}",
+            "```agent-command\n{\"command\": \"   \"}\n```",
+            "```agent-command\nls -la\n```",
+            "```agent-command\n{\"cmd\": \"ls\"}\n```",
+        ] {
+            assert!(parse_command_request(text).is_none(), "{:?}", text);
+        }
+    }
+}
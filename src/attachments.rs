@@ -0,0 +1,308 @@
+// src/attachments.rs
+use crate::config::Config;
+use crate::error::Result;
+use crate::llm::message::Image;
+use anyhow::{anyhow, Context};
+use base64::Engine as _;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+// --- Constants ---
+// Bytes inspected when deciding whether a file is binary
+const BINARY_SNIFF_BYTES: usize = 8192;
+// Marks an image reference: `@img:path`
+pub const IMAGE_PREFIX: &str = "img:";
+
+// --- Attached File ---
+#[derive(Debug, Clone)]
+pub struct AttachedFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub truncated: bool,
+}
+
+// --- Expansion Result ---
+#[derive(Debug, Clone)]
+pub struct Expansion {
+    // The prompt with the file contents appended
+    pub prompt: String,
+    pub files: Vec<AttachedFile>,
+    // Images referenced with `@img:path`, sent alongside the prompt
+    pub images: Vec<(PathBuf, Image)>,
+    // References that matched nothing or were skipped, with the reason
+    pub skipped: Vec<(String, String)>,
+}
+
+// --- Reference Parsing ---
+// An `@` reference starts at the beginning of the line or after whitespace
+// (so e-mail addresses are left alone) and runs until the next whitespace.
+// Returns (byte start, byte end, path) for each reference.
+pub fn find_references(input: &str) -> Vec<(usize, usize, &str)> {
+    let mut references = Vec::new();
+    let mut previous: Option<char> = None;
+    for (i, c) in input.char_indices() {
+        if c == '@' && previous.is_none_or(char::is_whitespace) {
+            let end = input[i..]
+                .find(char::is_whitespace)
+                .map_or(input.len(), |offset| i + offset);
+            if end > i + 1 {
+                references.push((i, end, &input[i + 1..end]));
+            }
+        }
+        previous = Some(c);
+    }
+    references
+}
+
+// Expands a leading "~/" to the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+// Whether a reference (optionally "img:"-prefixed) names an existing file;
+// used to highlight references while typing. Glob patterns give None: they
+// would be expanded on every keystroke.
+pub fn reference_exists(reference: &str) -> Option<bool> {
+    match reference.strip_prefix(IMAGE_PREFIX) {
+        Some(image_path) => Some(expand_home(image_path).is_file()),
+        None if is_pattern(reference) => None,
+        None => Some(expand_home(reference).is_file()),
+    }
+}
+
+fn is_pattern(reference: &str) -> bool {
+    reference.contains(['*', '?', '['])
+}
+
+fn resolve_reference(reference: &str) -> Result<Vec<PathBuf>> {
+    let expanded = expand_home(reference);
+    let pattern = expanded.to_string_lossy();
+    if is_pattern(&pattern) {
+        let mut paths: Vec<PathBuf> = glob::glob(&pattern)
+            .context(format!("Invalid glob pattern '{}'", reference))?
+            .filter_map(|entry| entry.ok())
+            .filter(|path| path.is_file())
+            .collect();
+        paths.sort();
+        Ok(paths)
+    } else if expanded.is_file() {
+        Ok(vec![expanded])
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+// --- File Reading ---
+
+fn is_binary(sample: &[u8]) -> bool {
+    sample.contains(&0) || std::str::from_utf8(sample).is_err_and(|e| e.error_len().is_some())
+}
+
+// Reads at most `max_bytes`, cutting back to a UTF-8 boundary
+pub fn read_text_file(path: &Path, max_bytes: usize) -> Result<(String, u64, bool)> {
+    let size = fs::metadata(path).context(format!("Failed to stat {:?}", path))?.len();
+    let mut buf = Vec::new();
+    fs::File::open(path)
+        .context(format!("Failed to open {:?}", path))?
+        .take(max_bytes as u64)
+        .read_to_end(&mut buf)
+        .context(format!("Failed to read {:?}", path))?;
+
+    if is_binary(&buf[..buf.len().min(BINARY_SNIFF_BYTES)]) {
+        return Err(anyhow!("binary file"));
+    }
+    let truncated = size > buf.len() as u64;
+    let text = match String::from_utf8(buf) {
+        Ok(text) => text,
+        Err(e) => {
+            let valid = e.utf8_error().valid_up_to();
+            let mut bytes = e.into_bytes();
+            bytes.truncate(valid);
+            String::from_utf8(bytes).unwrap_or_default()
+        }
+    };
+    Ok((text, size, truncated))
+}
+
+// --- Images ---
+
+// MIME type from the file's magic bytes; only formats every vision API accepts
+fn image_mime_type(header: &[u8]) -> Option<&'static str> {
+    match header {
+        [0x89, b'P', b'N', b'G', ..] => Some("image/png"),
+        [0xFF, 0xD8, 0xFF, ..] => Some("image/jpeg"),
+        [b'G', b'I', b'F', b'8', ..] => Some("image/gif"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("image/webp"),
+        _ => None,
+    }
+}
+
+// Reads an image file and base64-encodes it for an inline request part
+pub fn load_image(path: &Path, max_bytes: usize) -> Result<Image> {
+    let size = fs::metadata(path).context(format!("Failed to stat {:?}", path))?.len();
+    if size > max_bytes as u64 {
+        return Err(anyhow!("image is {} bytes (limit {})", size, max_bytes));
+    }
+    let bytes = fs::read(path).context(format!("Failed to read {:?}", path))?;
+    let mime_type = image_mime_type(&bytes)
+        .ok_or_else(|| anyhow!("not a PNG, JPEG, GIF or WebP image"))?;
+    Ok(Image {
+        mime_type: mime_type.to_string(),
+        data: base64::engine::general_purpose::STANDARD.encode(&bytes),
+    })
+}
+
+// Language tag for the code fence, taken from the file extension
+pub fn fence_language(path: &Path) -> &str {
+    match path.extension().and_then(|e| e.to_str()).unwrap_or("") {
+        "rs" => "rust",
+        "py" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "ts" => "typescript",
+        "sh" | "bash" => "bash",
+        "md" => "markdown",
+        "yml" => "yaml",
+        "h" => "c",
+        "hpp" | "cc" | "cxx" => "cpp",
+        other => other,
+    }
+}
+
+// Formats file contents as a labeled fenced block. The fence is made longer
+// than any backtick run in the contents so it cannot be closed early.
+pub fn fenced_block(label: &str, language: &str, contents: &str) -> String {
+    let longest_run = contents
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    let mut block = String::new();
+    let _ = writeln!(block, "{}", label);
+    let _ = writeln!(block, "{}{}", fence, language);
+    let _ = write!(block, "{}", contents);
+    if !contents.ends_with('\n') {
+        block.push('\n');
+    }
+    let _ = writeln!(block, "{}", fence);
+    block
+}
+
+// --- Expansion ---
+// Replaces `@path` / `@glob` references with the file contents, appended to
+// the prompt as fenced, labeled blocks. `@img:path` references are loaded as
+// images instead.
+pub fn expand_file_references(input: &str, config: &Config) -> Result<Expansion> {
+    let references = find_references(input);
+    let mut expansion = Expansion { prompt: input.to_string(), files: Vec::new(), images: Vec::new(), skipped: Vec::new() };
+    if references.is_empty() {
+        return Ok(expansion);
+    }
+
+    let mut blocks = Vec::new();
+    // Drop the '@' markers of resolved references; the paths stay in the text
+    let mut prompt = String::with_capacity(input.len());
+    let mut last = 0;
+    for (start, end, reference) in references {
+        prompt.push_str(&input[last..start]);
+        last = end;
+
+        if let Some(image_path) = reference.strip_prefix(IMAGE_PREFIX) {
+            prompt.push_str(image_path);
+            let path = expand_home(image_path);
+            match load_image(&path, config.image_max_bytes) {
+                Ok(image) => expansion.images.push((path, image)),
+                Err(e) => expansion.skipped.push((reference.to_string(), format!("{:#}", e))),
+            }
+            continue;
+        }
+
+        // Leave unmatched or unusable references untouched (they may not be
+        // paths at all, e.g. "@[user]")
+        let paths = match resolve_reference(reference) {
+            Ok(paths) if !paths.is_empty() => paths,
+            Ok(_) => {
+                prompt.push_str(&input[start..end]);
+                expansion.skipped.push((reference.to_string(), "no matching file".to_string()));
+                continue;
+            }
+            Err(e) => {
+                prompt.push_str(&input[start..end]);
+                expansion.skipped.push((reference.to_string(), format!("{:#}", e)));
+                continue;
+            }
+        };
+        prompt.push_str(reference);
+        for path in paths {
+            if expansion.files.iter().any(|f| f.path == path) {
+                continue;
+            }
+            if expansion.files.len() >= config.attachment_max_files {
+                expansion.skipped.push((path.display().to_string(), format!("more than {} files", config.attachment_max_files)));
+                continue;
+            }
+            match read_text_file(&path, config.attachment_max_bytes) {
+                Ok((text, size, truncated)) => {
+                    debug!("Attaching {:?} ({} bytes, truncated: {})", path, size, truncated);
+                    let mut contents = text;
+                    if truncated {
+                        let shown = contents.len();
+                        let _ = write!(
+                            contents,
+                            "\n[... truncated: showing the first {} of {} bytes ...]\n",
+                            shown, size
+                        );
+                    }
+                    let label = format!("File: {}", path.display());
+                    blocks.push(fenced_block(&label, fence_language(&path), &contents));
+                    expansion.files.push(AttachedFile { path, size, truncated });
+                }
+                Err(e) => expansion.skipped.push((path.display().to_string(), format!("{:#}", e))),
+            }
+        }
+    }
+    prompt.push_str(&input[last..]);
+
+    if !blocks.is_empty() {
+        prompt.push_str("\n\n");
+        prompt.push_str(&blocks.join("\n"));
+    }
+    expansion.prompt = prompt;
+    Ok(expansion)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_and_unmatched_references_stay_in_the_prompt() {
+        let input = "Mention @[user] or @[ and mail me@example.com";
+        let expansion = expand_file_references(input, &Config::default()).unwrap();
+        assert_eq!(expansion.prompt, input);
+        assert!(expansion.files.is_empty());
+        let skipped: Vec<&str> = expansion.skipped.iter().map(|(reference, _)| reference.as_str()).collect();
+        assert_eq!(skipped, ["[user]", "["]);
+        assert!(expansion.skipped[1].1.starts_with("Invalid glob pattern"));
+    }
+
+    #[test]
+    fn truncated_files_report_the_bytes_shown() {
+        let path = std::env::temp_dir().join(format!("llm-chat-attachment-test-{}.txt", std::process::id()));
+        // Three-byte characters: a 10-byte limit cuts back to 9 bytes
+        fs::write(&path, "€".repeat(10)).unwrap();
+        let config = Config { attachment_max_bytes: 10, ..Config::default() };
+        let expansion = expand_file_references(&format!("Read @{}", path.display()), &config);
+        fs::remove_file(&path).unwrap();
+
+        let expansion = expansion.unwrap();
+        assert!(expansion.files[0].truncated);
+        assert!(expansion.prompt.contains("[... truncated: showing the first 9 of 30 bytes ...]"));
+    }
+}
@@ -0,0 +1,227 @@
+// src/cli/compare.rs
+
+// --- Imports ---
+use crate::cli::repl::{format_cost, render_response, SessionState};
+use crate::config::{Config, LlmProvider};
+use crate::error::Result;
+use crate::llm::message::Message;
+use crate::llm::usage::Usage;
+use crate::llm::{self, Completion, Generation, RequestOptions};
+use anyhow::{anyhow, Context};
+use reqwest::Client;
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+use tracing::{debug, error};
+
+// --- Constants ---
+const COMPARE_USAGE: &str =
+    "Usage: /compare <provider[:model]> <provider[:model]> ... [--report <file.md|file.json>] -- <prompt>";
+
+// --- Compare Structs ---
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CompareTarget {
+    pub provider: LlmProvider,
+    pub model: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompareResult {
+    pub target: CompareTarget,
+    pub latency_ms: u128,
+    pub usage: Option<Usage>,
+    pub response: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct CompareReport<'a> {
+    prompt: &'a str,
+    results: &'a [CompareResult],
+}
+
+// --- Argument Parsing ---
+
+// Parses "provider" or "provider:model"; only the first ':' separates the
+// provider so Ollama tags like "llama3:8b" survive intact.
+pub fn parse_target(spec: &str, config: &Config) -> Result<CompareTarget> {
+    let (provider_name, model) = match spec.split_once(':') {
+        Some((p, m)) if !m.is_empty() => (p, Some(m)),
+        Some((p, _)) => (p, None),
+        None => (spec, None),
+    };
+    let provider = LlmProvider::from_config_name(provider_name)
+        .ok_or_else(|| anyhow!("Unknown provider '{}' in target '{}'", provider_name, spec))?;
+    let model = model
+        .map(String::from)
+        .unwrap_or_else(|| config.get_provider_model(&provider).to_string());
+    Ok(CompareTarget { provider, model })
+}
+
+// --- Command Handler ---
+pub async fn handle_compare_command(
+    config: &Config,
+    client: &Client,
+    state: &mut SessionState,
+    args_str: &str,
+) -> Result<()> {
+    // The standalone " -- " separates targets/options from the prompt
+    let Some((targets_str, prompt)) = args_str.split_once(" -- ").map(|(t, p)| (t, p.trim())) else {
+        println!("{}", COMPARE_USAGE);
+        println!("---");
+        return Ok(());
+    };
+
+    let mut report_path = None;
+    let mut specs = Vec::new();
+    let mut tokens = targets_str.split_whitespace();
+    while let Some(token) = tokens.next() {
+        if token == "--report" {
+            report_path = Some(tokens.next().ok_or_else(|| anyhow!("--report requires a file path"))?);
+        } else {
+            specs.push(token);
+        }
+    }
+
+    if specs.is_empty() || prompt.is_empty() {
+        println!("{}", COMPARE_USAGE);
+        println!("---");
+        return Ok(());
+    }
+
+    let targets = specs
+        .iter()
+        .map(|spec| parse_target(spec, config))
+        .collect::<Result<Vec<_>>>()?;
+
+    let blocked = state.check_budget(config);
+    if let Some(target) = targets.iter().find(|t| blocked.contains(&t.provider)) {
+        println!("Refusing to compare: {} is a paid provider and the monthly hard budget is exceeded.", target.provider);
+        println!("---");
+        return Ok(());
+    }
+
+    println!("... comparing {} targets ...", targets.len());
+    let results = run_compare(config, client, &targets, prompt).await;
+
+    for result in &results {
+        println!("=== {}:{} ({} ms) ===", result.target.provider, result.target.model, result.latency_ms);
+        match (&result.response, &result.error) {
+            (Some(text), _) => render_response(text),
+            (None, Some(e)) => eprintln!("Error: {}", e),
+            (None, None) => {}
+        }
+        if let (Some(usage), Some(text)) = (&result.usage, &result.response) {
+            let generation = Generation {
+                text: text.clone(),
+                usage: usage.clone(),
+                provider: result.target.provider.clone(),
+                model: result.target.model.clone(),
+                tool_calls: Vec::new(),
+            };
+            let request_cost = state.record_generation(config, &generation);
+            state.record_exchange(config, prompt, &generation, None, request_cost);
+            println!("[{}{}]", usage, format_cost(request_cost));
+        }
+        println!("---");
+    }
+
+    if let Some(path) = report_path {
+        write_report(Path::new(path), prompt, &results)?;
+        println!("Comparison report written to {}", path);
+        println!("---");
+    }
+    Ok(())
+}
+
+// --- Concurrent Execution ---
+// Fires the prompt at every target at once; results keep the target order.
+pub async fn run_compare(
+    config: &Config,
+    client: &Client,
+    targets: &[CompareTarget],
+    prompt: &str,
+) -> Vec<CompareResult> {
+    let mut tasks = JoinSet::new();
+    for (index, target) in targets.iter().cloned().enumerate() {
+        let mut target_config = config.clone();
+        target_config.set_provider_model(&target.provider, target.model.clone());
+        let client = client.clone();
+        let prompt = prompt.to_string();
+        tasks.spawn(async move {
+            let start = Instant::now();
+            let messages = [Message::user(prompt)];
+            let result = llm::generate(&client, &target_config, &target.provider, &messages, &RequestOptions::default()).await;
+            (index, target, start.elapsed(), result)
+        });
+    }
+
+    let mut results: Vec<(usize, CompareResult)> = Vec::with_capacity(targets.len());
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((index, target, elapsed, result)) => {
+                debug!("Compare target {}:{} finished in {:?}", target.provider, target.model, elapsed);
+                results.push((index, to_compare_result(target, elapsed, result)));
+            }
+            Err(e) => error!("Compare task panicked: {:?}", e),
+        }
+    }
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, r)| r).collect()
+}
+
+fn to_compare_result(target: CompareTarget, elapsed: Duration, result: Result<Completion>) -> CompareResult {
+    let (response, usage, error) = match result {
+        Ok(Completion { text, usage, .. }) => (Some(text), Some(usage), None),
+        Err(e) => (None, None, Some(format!("{:#}", e))),
+    };
+    CompareResult { target, latency_ms: elapsed.as_millis(), usage, response, error }
+}
+
+// --- Report Writing ---
+fn write_report(path: &Path, prompt: &str, results: &[CompareResult]) -> Result<()> {
+    let is_json = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    let contents = if is_json {
+        serde_json::to_string_pretty(&CompareReport { prompt, results })?
+    } else {
+        markdown_report(prompt, results)
+    };
+    std::fs::write(path, contents).context(format!("Failed to write comparison report to {:?}", path))
+}
+
+fn markdown_report(prompt: &str, results: &[CompareResult]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# Model Comparison\n");
+    let _ = writeln!(out, "## Prompt\n\n{}\n", prompt);
+    let _ = writeln!(out, "| Provider | Model | Latency (ms) | Prompt Tokens | Completion Tokens | Status |");
+    let _ = writeln!(out, "|---|---|---|---|---|---|");
+    for r in results {
+        let status = if r.error.is_some() { "error" } else { "ok" };
+        let tokens = |t: Option<u32>| t.map_or_else(|| "-".to_string(), |t| t.to_string());
+        let prompt_tokens = tokens(r.usage.as_ref().and_then(|u| u.prompt_tokens));
+        let completion_tokens = tokens(r.usage.as_ref().and_then(|u| u.completion_tokens));
+        let _ = writeln!(
+            out,
+            "| {} | {} | {} | {} | {} | {} |",
+            r.target.provider, r.target.model, r.latency_ms, prompt_tokens, completion_tokens, status
+        );
+    }
+    for r in results {
+        let _ = writeln!(out, "\n## {}:{}\n", r.target.provider, r.target.model);
+        match (&r.response, &r.error) {
+            (Some(text), _) => {
+                let _ = writeln!(out, "{}", text.trim());
+            }
+            (None, Some(e)) => {
+                let _ = writeln!(out, "**Error:** {}", e);
+            }
+            (None, None) => {}
+        }
+    }
+    out
+}
@@ -1,14 +1,23 @@
 // src/cli/helper.rs
 use rustyline::completion::{Completer, Pair};
 use rustyline::highlight::Highlighter;
-use rustyline::hint::Hinter;
-use rustyline::validate::Validator;
+use rustyline::hint::{Hint, Hinter, HistoryHinter};
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
 use rustyline::{Context, Helper, Result as RustylineResult};
+use crate::attachments;
+use crate::config::LlmProvider;
+use crate::export::ExportFormat;
+use std::borrow::Cow;
+use crate::conversation;
+use crate::templates;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 // Define the app commands that we want to complete
-const APP_COMMANDS: [&str; 12] = [
+const APP_COMMANDS: [&str; 40] = [
     // General
-    "/help", "/status", "/use", "/config", "/quit", "/exit","/model","/model_list","/select_model",
+    "/help", "/status", "/use", "/config", "/quit", "/exit","/model","/model_list","/select_model","/compare","/usage","/cost","/context","/compact","/clear","/session", "/export", "/import", "/search", "/show","/retry","/edit","/undo","/branches","/checkout","/run","/image","/schema","/code","/t","/editor","/embed","/index","/ask","/agent","/tools","/mcp",
     // Gemini
     "/gemini_config",
     // Groq
@@ -17,12 +26,130 @@ const APP_COMMANDS: [&str; 12] = [
     "/huggingface_config",
 ];
 
+// Argument hints shown after a command and a space
+const COMMAND_USAGE_HINTS: &[(&str, &str)] = &[
+    ("/use", "<provider>"),
+    ("/model", "<name>"),
+    ("/compare", "<p:m> <p:m> ... [--report f.md|f.json] -- <prompt>"),
+    ("/context", "[strategy <s>]"),
+    ("/session", "save|load <name> | list"),
+    ("/export", "md|html|json [path]"),
+    ("/import", "<file> [n|title]"),
+    ("/search", "<terms>"),
+    ("/show", "<id>"),
+    ("/retry", "[p[:m]] [-t temp]"),
+    ("/edit", "[text]"),
+    ("/checkout", "<branch>"),
+    ("/run", "<cmd>"),
+    ("/image", "<path>"),
+    ("/schema", "<file>|off"),
+    ("/code", "[save <n> <path> | copy <n> | run <n>]"),
+    ("/t", "[name] [var=val ...]"),
+    ("/editor", "[text]"),
+    ("/embed", "[-f file] [-o out] [--format jsonl|f32] <text>"),
+    ("/index", "[dir]"),
+    ("/ask", "<question>"),
+    ("/agent", "[on|off]"),
+    ("/tools", "[on|off]"),
+    ("/mcp", "list | tools"),
+    ("/gemini_config", "[temp <v|reset>] [top_p <v|reset>] [max_tokens <v|reset>] [reset]"),
+];
+
+// --- Colors ---
+const COLOR_COMMAND: &str = "\x1b[1;36m";
+const COLOR_UNKNOWN: &str = "\x1b[31m";
+const COLOR_SHELL: &str = "\x1b[33m";
+const COLOR_REFERENCE: &str = "\x1b[4;32m";
+const COLOR_MISSING: &str = "\x1b[4;31m";
+const COLOR_PATTERN: &str = "\x1b[4m";
+const COLOR_HINT: &str = "\x1b[2m";
+const COLOR_RESET: &str = "\x1b[0m";
+
+// Parameter names accepted by /gemini_config
+const GEMINI_CONFIG_PARAMS: [&str; 4] = ["temp", "top_p", "max_tokens", "reset"];
+
+pub fn is_app_command(name: &str) -> bool {
+    APP_COMMANDS.iter().any(|cmd| cmd[1..] == *name)
+}
+
+// --- Model Cache ---
+// Model names from the last list_models call per provider, shared between
+// the REPL (which fills it) and completion. Entries expire after `ttl`.
+// When the list was fetched, and the model names
+type CachedModels = (Instant, Vec<String>);
+
+#[derive(Debug, Clone, Default)]
+pub struct ModelCache {
+    entries: Arc<Mutex<HashMap<String, CachedModels>>>,
+    ttl: Duration,
+}
+
+impl ModelCache {
+    pub fn new(ttl: Duration) -> Self {
+        ModelCache { entries: Arc::default(), ttl }
+    }
+
+    pub fn store(&self, provider: &LlmProvider, models: &[String]) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert(provider.get_provider_config_name().to_string(), (Instant::now(), models.to_vec()));
+        }
+    }
+
+    // The cached list, unless it has expired
+    pub fn get(&self, provider: &LlmProvider) -> Option<Vec<String>> {
+        let entries = self.entries.lock().ok()?;
+        let (fetched, models) = entries.get(provider.get_provider_config_name())?;
+        (fetched.elapsed() < self.ttl).then(|| models.clone())
+    }
+}
+
 #[derive(Helper)]
-pub struct ReplHelper {}
+pub struct ReplHelper {
+    // User-defined aliases, completed like the built-in commands
+    aliases: Vec<String>,
+    models: ModelCache,
+    // Kept in sync by the REPL so /model completes the right list
+    active_provider: LlmProvider,
+    history_hinter: HistoryHinter,
+}
 
 impl ReplHelper {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(aliases: Vec<String>, models: ModelCache, active_provider: LlmProvider) -> Self {
+        Self { aliases, models, active_provider, history_hinter: HistoryHinter::new() }
+    }
+
+    fn is_known_command(&self, command: &str) -> bool {
+        APP_COMMANDS.contains(&command) || self.aliases.iter().any(|alias| alias == command)
+    }
+
+    pub fn set_active_provider(&mut self, provider: &LlmProvider) {
+        self.active_provider = provider.clone();
+    }
+
+    // Candidates for the argument being typed, given the command and the
+    // arguments before it. `None` means "complete a file path".
+    fn argument_candidates(&self, command: &str, previous: &[&str]) -> Option<Vec<String>> {
+        let words = |list: &[&str]| Some(list.iter().map(|w| w.to_string()).collect());
+        match (command, previous) {
+            ("/use", []) => Some(LlmProvider::ALL.iter().map(|p| p.get_provider_config_name().to_string()).collect()),
+            ("/model", []) => Some(self.models.get(&self.active_provider).unwrap_or_default()),
+            // Parameter names, then "reset" as a value
+            ("/gemini_config", args) if args.len() % 2 == 0 => words(&GEMINI_CONFIG_PARAMS),
+            ("/gemini_config", [.., last]) if *last != "reset" => words(&["reset"]),
+            ("/gemini_config", _) => words(&GEMINI_CONFIG_PARAMS),
+            ("/session", []) => words(&["save", "load", "list"]),
+            ("/session", ["load" | "save"]) => Some(conversation::session_names()),
+            ("/export", []) => words(&ExportFormat::NAMES),
+            ("/export", [_]) => None,
+            ("/import", []) => None,
+            ("/t", []) => Some(templates::list_names()),
+            ("/agent" | "/tools", []) => words(&["on", "off"]),
+            ("/code", []) => words(&["save", "copy", "run"]),
+            ("/mcp", []) => words(&["list", "tools"]),
+            ("/context", []) => words(&["strategy"]),
+            ("/image" | "/index" | "/schema", []) => None,
+            _ => Some(Vec::new()),
+        }
     }
 }
 
@@ -36,19 +163,49 @@ impl Completer for ReplHelper {
         pos: usize,
         _ctx: &Context<'_>,
     ) -> RustylineResult<(usize, Vec<Self::Candidate>)> {
+        // @file references can appear anywhere in the line. Whitespace may be
+        // multi-byte (e.g. a non-breaking space), so skip the whole character.
+        let word_start = line[..pos]
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        if line[word_start..pos].starts_with('@') {
+            let filename_completer = rustyline::completion::FilenameCompleter::new();
+            let marker = if line[word_start..pos].starts_with("@img:") { 5 } else { 1 };
+            let path_start = word_start + marker;
+            return filename_completer
+                .complete(&line[path_start..pos], pos - path_start, _ctx)
+                .map(|(replace_offset, candidates)| (path_start + replace_offset, candidates));
+        }
+
+        // Arguments of a command, depending on the command and position
+        if line.starts_with('/') {
+            if let Some((command, args)) = line[..pos].split_once(' ') {
+                let previous: Vec<&str> = args[..args.len() - (pos - word_start)].split_whitespace().collect();
+                let partial = &line[word_start..pos];
+                return match self.argument_candidates(command, &previous) {
+                    Some(options) => Ok((
+                        word_start,
+                        options
+                            .into_iter()
+                            .filter(|option| option.starts_with(partial))
+                            .map(|option| Pair { display: option.clone(), replacement: option })
+                            .collect(),
+                    )),
+                    None => rustyline::completion::FilenameCompleter::new()
+                        .complete(partial, partial.len(), _ctx)
+                        .map(|(offset, candidates)| (word_start + offset, candidates)),
+                };
+            }
+        }
+
         if line.starts_with('/') && pos > 0 {
-            let command_part = if let Some(space_idx) = line.find(' ') {
-                if pos <= space_idx {
-                    &line[1..pos]
-                } else {
-                    return Ok((pos, Vec::new()));
-                }
-            } else {
-                &line[1..pos]
-            };
+            // Arguments were handled above, so the cursor is in the command
+            let command_part = &line[1..pos];
 
             let mut completions = Vec::new();
-            for cmd in APP_COMMANDS.iter() {
+            for cmd in APP_COMMANDS.iter().copied().chain(self.aliases.iter().map(String::as_str)) {
                 if cmd.starts_with(&format!("/{}", command_part)) {
                     completions.push(Pair {
                         display: cmd.to_string(),
@@ -60,10 +217,9 @@ impl Completer for ReplHelper {
             Ok((start_pos, completions))
         } else if line.starts_with('!') {
             let filename_completer = rustyline::completion::FilenameCompleter::new();
-            let mut start_pos = 1;
-            while start_pos < line.len() && line.chars().nth(start_pos).map_or(false, |c| c.is_whitespace()) {
-                start_pos += 1;
-            }
+            // Skip the '!' (or '!!') marker and any following whitespace
+            let marker = if line.starts_with("!!") { 2 } else { 1 };
+            let start_pos = line.len() - line[marker..].trim_start().len();
             if pos >= start_pos {
                 filename_completer.complete(&line[start_pos..], pos - start_pos, _ctx)
                     .map(|(replace_offset, candidates)| (start_pos + replace_offset, candidates))
@@ -76,14 +232,136 @@ impl Completer for ReplHelper {
     }
 }
 
-// --- Implement other traits (optional, rustyline provides defaults via derive) ---
+// --- Hints ---
+// Usage after "/command " (display only), otherwise fish-style completion of
+// the line from history (accepted with the right arrow).
+pub enum ReplHint {
+    Usage(String),
+    History(String),
+}
+
+impl Hint for ReplHint {
+    fn display(&self) -> &str {
+        match self {
+            ReplHint::Usage(text) | ReplHint::History(text) => text,
+        }
+    }
+
+    fn completion(&self) -> Option<&str> {
+        match self {
+            ReplHint::Usage(_) => None,
+            ReplHint::History(text) => Some(text),
+        }
+    }
+}
+
 impl Hinter for ReplHelper {
-    type Hint = String;
-    fn hint(&self, _line: &str, _pos: usize, _ctx: &Context<'_>) -> Option<String> {
-        None
+    type Hint = ReplHint;
+    fn hint(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Option<ReplHint> {
+        if pos < line.len() {
+            return None;
+        }
+        if let Some(command) = line.strip_suffix(' ').filter(|c| !c.contains(' ')) {
+            if let Some((_, usage)) = COMMAND_USAGE_HINTS.iter().find(|(name, _)| *name == command) {
+                return Some(ReplHint::Usage(usage.to_string()));
+            }
+        }
+        self.history_hinter.hint(line, pos, ctx).map(ReplHint::History)
     }
 }
 
-impl Validator for ReplHelper {}
+// --- Multi-line Input ---
+// Enter continues the input while a ``` fence is open or the line ends with
+// a backslash.
+impl Validator for ReplHelper {
+    fn validate(&self, ctx: &mut ValidationContext<'_>) -> RustylineResult<ValidationResult> {
+        let input = ctx.input();
+        if in_open_fence(input) || input.ends_with('\\') {
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
 
-impl Highlighter for ReplHelper {}
+fn is_fence(line: &str) -> bool {
+    line.trim_start().starts_with("```")
+}
+
+fn in_open_fence(input: &str) -> bool {
+    input.lines().filter(|line| is_fence(line)).count() % 2 == 1
+}
+
+// Removes the continuation backslashes from accepted input, keeping the line
+// breaks. Backslashes inside fenced code are left alone.
+pub fn join_continuations(input: &str) -> String {
+    let mut joined = String::with_capacity(input.len());
+    let mut in_fence = false;
+    for line in input.split('\n') {
+        if is_fence(line) {
+            in_fence = !in_fence;
+        }
+        let line = if in_fence { line } else { line.strip_suffix('\\').unwrap_or(line) };
+        joined.push_str(line);
+        joined.push('\n');
+    }
+    joined.pop();
+    joined
+}
+
+// --- Highlighting ---
+// Commands are colored by whether they exist, `!` lines as shell, and `@`
+// references by whether they name existing files (globs are only underlined).
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        if line.starts_with('!') {
+            return Cow::Owned(format!("{}{}{}", COLOR_SHELL, line, COLOR_RESET));
+        }
+        let references = attachments::find_references(line);
+        if !line.starts_with('/') && references.is_empty() {
+            return Cow::Borrowed(line);
+        }
+
+        let mut highlighted = String::with_capacity(line.len() + 32);
+        let mut last = 0;
+        if line.starts_with('/') {
+            let end = line.find(char::is_whitespace).unwrap_or(line.len());
+            let command = &line[..end];
+            // Leave a prefix of a known command plain while it is being typed
+            let typing = end == line.len()
+                && APP_COMMANDS.iter().copied().chain(self.aliases.iter().map(String::as_str)).any(|c| c.starts_with(command));
+            if self.is_known_command(command) {
+                highlighted.push_str(&format!("{}{}{}", COLOR_COMMAND, command, COLOR_RESET));
+            } else if typing {
+                highlighted.push_str(command);
+            } else {
+                highlighted.push_str(&format!("{}{}{}", COLOR_UNKNOWN, command, COLOR_RESET));
+            }
+            last = end;
+        }
+        for (start, end, reference) in references {
+            if start < last {
+                continue;
+            }
+            let color = match attachments::reference_exists(reference) {
+                Some(true) => COLOR_REFERENCE,
+                Some(false) => COLOR_MISSING,
+                None => COLOR_PATTERN,
+            };
+            highlighted.push_str(&line[last..start]);
+            highlighted.push_str(&format!("{}{}{}", color, &line[start..end], COLOR_RESET));
+            last = end;
+        }
+        highlighted.push_str(&line[last..]);
+        Cow::Owned(highlighted)
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(format!("{}{}{}", COLOR_HINT, hint, COLOR_RESET))
+    }
+
+    // Re-highlight on every keystroke: colors depend on the whole line
+    fn highlight_char(&self, _line: &str, _pos: usize, _forced: bool) -> bool {
+        true
+    }
+}
@@ -1,4 +1,6 @@
 // src/cli/mod.rs
 
 pub mod repl;
-pub mod helper; 
\ No newline at end of file
+pub mod helper;
+pub mod compare;
+pub mod oneshot;
@@ -0,0 +1,59 @@
+// src/cli/oneshot.rs
+
+// --- Imports ---
+use crate::cli::repl::SessionState;
+use crate::config::Config;
+use crate::cost;
+use crate::error::Result;
+use crate::llm::message::Message;
+use crate::llm::{self, RequestOptions};
+use crate::structured::{self, ResponseSchema};
+use anyhow::anyhow;
+use reqwest::Client;
+use tracing::debug;
+
+// --- One-Shot Prompt ---
+// Non-interactive mode for scripts and pipelines: sends one prompt and writes
+// only the answer to stdout. Progress and errors go to stderr. With a schema
+// the answer is validated JSON; an invalid reply is re-prompted once and
+// otherwise reported as an error, so nothing invalid reaches stdout.
+pub async fn run_prompt(
+    config: &Config,
+    client: &Client,
+    prompt: &str,
+    schema: Option<&ResponseSchema>,
+) -> Result<()> {
+    let mut state = SessionState::new();
+    let blocked = cost::blocked_providers(config, &mut state.ledger);
+    let options = RequestOptions { response_schema: schema.map(|s| &s.schema), ..RequestOptions::default() };
+    let mut messages = vec![Message::user(prompt)];
+
+    let mut retried = false;
+    loop {
+        let generation = llm::generate_with_fallback(client, config, &messages, &options, &blocked).await?;
+        let request_cost = state.record_generation(config, &generation);
+        // History keeps the prompt as given, not the schema retry instruction
+        state.record_exchange(config, prompt, &generation, None, request_cost);
+        debug!("One-shot reply from {}:{} ({})", generation.provider, generation.model, generation.usage);
+
+        let Some(schema) = schema else {
+            println!("{}", generation.text);
+            return Ok(());
+        };
+        match schema.validate(&generation.text) {
+            Ok(value) => {
+                println!("{}", serde_json::to_string(&value)?);
+                return Ok(());
+            }
+            Err(errors) if !retried => {
+                eprintln!("Reply does not match the schema; asking again: {}", errors.join("; "));
+                retried = true;
+                messages.push(Message::assistant(generation.text));
+                messages.push(Message::user(structured::retry_prompt(&errors)));
+            }
+            Err(errors) => {
+                return Err(anyhow!("Reply does not match the schema: {}", errors.join("; ")));
+            }
+        }
+    }
+}
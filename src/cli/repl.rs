@@ -1,42 +1,219 @@
 // src/cli/repl.rs
 
 // --- Imports ---
-use crate::cli::helper::ReplHelper;
-use crate::config::{Config, LlmProvider};
+use crate::agent::{self, Approval};
+use crate::attachments;
+use crate::cli::compare;
+use crate::codeblocks::{self, CodeBlock};
+use crate::cli::helper::{self, ModelCache, ReplHelper};
+use crate::config::{self, Config, LlmProvider};
+use crate::context::{self, ContextStrategy};
+use crate::conversation::{self, Conversation};
+use crate::embed::{self, EmbedArgs};
+use crate::cost::{self, BudgetStatus, Ledger, LedgerEntry};
 use crate::error::Result;
-use crate::llm::{gemini, groq, ollama,huggingface};
+use crate::export::{self, ExportFormat, ExportInfo};
+use crate::history::{self, History};
+use crate::import;
+use crate::llm::message::{AnswerMeta, Image, Message, Role};
+use crate::mcp::{self, McpServer};
+use crate::rag::{self, Index};
+use crate::shell;
+use crate::structured::{self, ResponseSchema};
+use crate::summary;
+use crate::templates::{self, Template};
+use crate::llm::usage::UsageTotals;
+use crate::llm::{self, gemini, groq, ollama,huggingface, Generation, RequestOptions};
 use anyhow::{anyhow, Context};
+use clap::Parser;
 use reqwest::Client;
 use rustyline::error::ReadlineError;
 use rustyline::history::DefaultHistory;
-use rustyline::Editor;
+use rustyline::{DefaultEditor, Editor};
+use crate::tokenizer::Tokenizer;
+use crate::tools::ToolRegistry;
+use std::collections::{BTreeMap, HashMap};
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::time::Duration;
 use tracing::{debug, error, info, warn};
 
 // --- Constants ---
 const HISTORY_FILE: &str = "history.txt";
-const PROMPT_FORMAT: &str = "{}:{}{}";
 const UNKNOWN_COMMAND_MSG: &str = "Unknown command: '/{}'. Type '/help' for available commands.";
 const SHELL_COMMAND_USAGE: &str = "Usage: !<shell_command>";
-const APP_COMMAND_USAGE: &str = "Usage: /<command> [args...]";
+const SHELL_CAPTURE_USAGE: &str = "Usage: !!<shell_command> | /run <shell_command>";
+const RETRY_USAGE: &str = "Usage: /retry [provider[:model]] [--temperature <t>]";
+
+// --- Session State ---
+// Data that lives for the duration of one interactive session.
+#[derive(Debug, Default)]
+pub struct SessionState {
+    pub conversation: Conversation,
+    // Captured command output waiting to be sent with the next prompt
+    pub pending_context: Vec<String>,
+    // Images queued with /image for the next prompt
+    pub pending_images: Vec<(PathBuf, Image)>,
+    // Let the model request shell commands (see /agent)
+    pub agent_mode: bool,
+    // Offer the registered tools to the model (see /tools)
+    pub tools_enabled: bool,
+    pub tools: ToolRegistry,
+    // Schema set with /schema; replies must be JSON matching it
+    pub response_schema: Option<(PathBuf, ResponseSchema)>,
+    // Running MCP servers (their tools are also in `tools`)
+    pub mcp_servers: Vec<McpServer>,
+    // Directory index built with /index; searched by /ask
+    pub rag_index: Option<Index>,
+    // Retrieve from the index for every prompt (--rag)
+    pub rag_always: bool,
+    // Context window sizes looked up from the providers, keyed by "Provider:model"
+    pub context_windows: HashMap<String, u32>,
+    pub usage: UsageTotals,
+    // Keyed by "Provider:model"
+    pub usage_by_model: BTreeMap<String, UsageTotals>,
+    // USD spent this session on requests with a known price
+    pub cost: f64,
+    pub ledger: Ledger,
+    // Model lists per provider, shared with tab completion
+    pub model_cache: ModelCache,
+    // Prompt/answer database for /search and /show (None if it failed to open)
+    pub history: Option<History>,
+}
+
+impl SessionState {
+    pub fn new() -> Self {
+        let history = History::open()
+            .map_err(|e| warn!("History database unavailable: {:#}", e))
+            .ok();
+        SessionState { ledger: Ledger::load(), tools: ToolRegistry::with_builtins(), history, ..SessionState::default() }
+    }
+
+    // Stores an answered prompt in the history database (if enabled)
+    pub fn record_exchange(
+        &self,
+        config: &Config,
+        prompt: &str,
+        generation: &Generation,
+        temperature: Option<f32>,
+        cost: Option<f64>,
+    ) {
+        if !config.record_history || generation.text.trim().is_empty() {
+            return;
+        }
+        if let Some(history) = &self.history {
+            if let Err(e) = history.record(prompt, generation, temperature, cost) {
+                warn!("Failed to record exchange: {:#}", e);
+            }
+        }
+    }
+
+    // Launches the configured MCP servers and registers their tools
+    pub async fn start_mcp_servers(&mut self, config: &Config) {
+        if config.mcp_servers.is_empty() {
+            return;
+        }
+        println!("Starting {} MCP server(s)...", config.mcp_servers.len());
+        for server in mcp::start_servers(&config.mcp_servers).await {
+            self.tools.register_mcp(&server);
+            self.mcp_servers.push(server);
+        }
+    }
+
+    // Adds a finished request to the session totals and, for paid providers,
+    // to the monthly ledger. Returns the request cost if it could be priced.
+    pub fn record_generation(&mut self, config: &Config, generation: &Generation) -> Option<f64> {
+        self.usage.add(&generation.usage);
+        self.usage_by_model
+            .entry(format!("{}:{}", generation.provider, generation.model))
+            .or_default()
+            .add(&generation.usage);
+
+        let request_cost = cost::request_cost(config, &generation.provider, &generation.model, &generation.usage);
+        if let Some(c) = request_cost {
+            self.cost += c;
+        }
+        if generation.provider.is_paid() {
+            let entry = LedgerEntry {
+                timestamp: chrono::Local::now(),
+                provider: generation.provider.clone(),
+                model: generation.model.clone(),
+                prompt_tokens: generation.usage.prompt_tokens,
+                completion_tokens: generation.usage.completion_tokens,
+                cost: request_cost.unwrap_or(0.0),
+            };
+            if let Err(e) = self.ledger.record(&entry) {
+                warn!("Failed to record ledger entry: {:?}", e);
+            }
+        }
+        request_cost
+    }
+
+    // Context window for the active model: config override, then cached or
+    // provider-reported size, then the configured default.
+    pub async fn context_window(&mut self, config: &Config, client: &Client) -> u32 {
+        let provider = &config.active_provider;
+        let model = config.get_active_model_name();
+        let override_key = format!("{}:{}", provider.get_provider_config_name(), model);
+        if let Some(&window) = config.context_window_overrides.get(&override_key) {
+            return window;
+        }
+        let cache_key = format!("{}:{}", provider, model);
+        if let Some(&window) = self.context_windows.get(&cache_key) {
+            return window;
+        }
+        let window = match llm::context_length(client, config, provider).await {
+            Ok(Some(window)) => window,
+            Ok(None) => config.default_context_window,
+            Err(e) => {
+                debug!("Could not fetch context length for {}: {:?}", cache_key, e);
+                config.default_context_window
+            }
+        };
+        self.context_windows.insert(cache_key, window);
+        window
+    }
+
+    // Warns about the soft budget and returns the providers blocked by the hard one
+    pub fn check_budget(&mut self, config: &Config) -> Vec<LlmProvider> {
+        match cost::budget_status(config, &mut self.ledger) {
+            BudgetStatus::Ok => {}
+            BudgetStatus::SoftExceeded => println!(
+                "Warning: monthly spend ${:.4} has reached the soft budget (${:.2}).",
+                self.ledger.month_total,
+                config.monthly_soft_budget.unwrap_or_default()
+            ),
+            BudgetStatus::HardExceeded => println!(
+                "Monthly spend ${:.4} has reached the hard budget (${:.2}); paid providers are disabled.",
+                self.ledger.month_total,
+                config.monthly_hard_budget.unwrap_or_default()
+            ),
+        }
+        cost::blocked_providers(config, &mut self.ledger)
+    }
+}
 
 // --- History File Helper ---
 fn get_history_path() -> PathBuf {
-    let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
-    path.push("llm-chat");
-    std::fs::create_dir_all(&path).ok();
-    path.push(HISTORY_FILE);
-    path
+    config::config_dir().join(HISTORY_FILE)
 }
 
 // --- Main REPL Function ---
-pub async fn run_interactive(config: &mut Config, client: &Client) -> Result<()> {
+pub async fn run_interactive(config: &mut Config, client: &Client, rag_dir: Option<&Path>) -> Result<()> {
     info!("Starting interactive LLM chat session.");
 
+    let mut state = SessionState::new();
+    state.model_cache = ModelCache::new(Duration::from_secs(config.model_cache_ttl_secs));
+
     // --- Setup Rustyline Editor ---
-    let helper = ReplHelper::new();
+    let aliases = config
+        .aliases
+        .keys()
+        .filter(|name| !helper::is_app_command(name))
+        .map(|name| format!("/{}", name))
+        .collect();
+    let helper = ReplHelper::new(aliases, state.model_cache.clone(), config.active_provider.clone());
     let mut rl = Editor::<ReplHelper, DefaultHistory>::new()?;
     rl.set_helper(Some(helper));
     let history_path = get_history_path();
@@ -45,6 +222,18 @@ pub async fn run_interactive(config: &mut Config, client: &Client) -> Result<()>
     }
     // --- End Setup ---
 
+    state.start_mcp_servers(config).await;
+    if let Some(dir) = rag_dir {
+        // Retrieval is on for the whole session; a failed index leaves it off
+        match build_index(config, client, &mut state, dir).await {
+            Ok(()) => state.rag_always = true,
+            Err(e) => {
+                error!("Indexing {:?} failed: {:?}", dir, e);
+                eprintln!("Error indexing {}: {}", dir.display(), e);
+            }
+        }
+    }
+
     // --- Print initial connection status ---
     print_initial_status(config);
 
@@ -52,12 +241,17 @@ pub async fn run_interactive(config: &mut Config, client: &Client) -> Result<()>
     loop {
         // Display prompt with current provider/model
         let prompt_string = format_prompt(config);
+        if let Some(helper) = rl.helper_mut() {
+            helper.set_active_provider(&config.active_provider);
+        }
 
         // Read user input
         let readline_result = rl.readline(&prompt_string);
         match readline_result {
             Ok(line) => {
-                let input = line.trim();
+                // Multi-line entries keep their line breaks, minus the `\` markers
+                let joined = helper::join_continuations(&line);
+                let input = joined.trim();
 
                 // Add non-empty lines to history
                 if !input.is_empty() {
@@ -71,34 +265,26 @@ pub async fn run_interactive(config: &mut Config, client: &Client) -> Result<()>
                     continue;
                 }
 
-                // Check for exit commands
-                if is_exit_command(input) {
-                    break;
-                }
-
-                // --- Dispatch Input ---
-                if input.starts_with('!') {
-                    // Handle Shell Command
-                    if let Err(e) = handle_shell_command(input) {
-                        error!("Shell command failed: {:?}", e);
-                        eprintln!("Error executing shell command: {}", e);
-                        eprintln!("---");
+                // Aliases expand into one or more steps, run in order; an exit
+                // step ends the session once the steps before it have run
+                let steps = expand_alias(input, config).unwrap_or_else(|| vec![input.to_string()]);
+                let mut exit = false;
+                for step in &steps {
+                    if is_exit_command(step) {
+                        exit = true;
+                        break;
                     }
-                } else if input.starts_with('/') {
-                    // Handle App Command
-                    if let Err(e) = handle_app_command(input, config, client).await {
-                        error!("App command failed: {:?}", e);
-                        eprintln!("Error executing app command: {}", e);
-                        eprintln!("---");
+                    if steps.len() > 1 {
+                        println!("> {}", step);
                     }
-                } else {
-                    // Handle LLM Prompt
-                    if let Err(e) = handle_llm_prompt(input, config, client).await {
-                        error!("LLM prompt failed: {:?}", e);
-                        eprintln!("Error generating LLM response: {}", e);
-                        eprintln!("---");
+                    if !dispatch_input(step, config, client, &mut state).await && steps.len() > 1 {
+                        eprintln!("Alias stopped after a failed step.");
+                        break;
                     }
                 }
+                if exit {
+                    break;
+                }
             }
             // Handle REPL specific errors
             Err(ReadlineError::Interrupted) => {
@@ -126,6 +312,74 @@ pub async fn run_interactive(config: &mut Config, client: &Client) -> Result<()>
     Ok(())
 }
 
+// --- Input Dispatch ---
+// Routes one line (or alias step) to the shell, app command or LLM handler.
+// Errors are reported here so the REPL keeps going; returns false if the step
+// failed (an error, a failed request, a command that exited non-zero or a
+// command that did nothing because of bad arguments).
+async fn dispatch_input(input: &str, config: &mut Config, client: &Client, state: &mut SessionState) -> bool {
+    if let Some(command_str) = input.strip_prefix("!!") {
+        // Handle Shell Command whose output goes to the model
+        handle_shell_capture(command_str, config, state).unwrap_or_else(|e| {
+            error!("Shell command failed: {:?}", e);
+            eprintln!("Error executing shell command: {}", e);
+            eprintln!("---");
+            false
+        })
+    } else if input.starts_with('!') {
+        // Handle Shell Command
+        handle_shell_command(input).unwrap_or_else(|e| {
+            error!("Shell command failed: {:?}", e);
+            eprintln!("Error executing shell command: {}", e);
+            eprintln!("---");
+            false
+        })
+    } else if input.starts_with('/') {
+        // Handle App Command
+        handle_app_command(input, config, client, state).await.unwrap_or_else(|e| {
+            error!("App command failed: {:?}", e);
+            eprintln!("Error executing app command: {}", e);
+            eprintln!("---");
+            false
+        })
+    } else {
+        // Handle LLM Prompt
+        let retrieve = state.rag_always;
+        handle_llm_prompt(input, config, client, state, retrieve, None).await.unwrap_or_else(|e| {
+            error!("LLM prompt failed: {:?}", e);
+            eprintln!("Error generating LLM response: {}", e);
+            eprintln!("---");
+            false
+        })
+    }
+}
+
+// Expands a user-defined alias ("/fast groq" with aliases.fast = [...]) into
+// its steps. `$*` in a step takes the arguments; otherwise they are appended
+// to the last step. Built-in commands cannot be shadowed, and steps are not
+// expanded again.
+fn expand_alias(input: &str, config: &Config) -> Option<Vec<String>> {
+    let (name, args) = input.strip_prefix('/')?.split_once(char::is_whitespace).unwrap_or((&input[1..], ""));
+    if helper::is_app_command(name) {
+        return None;
+    }
+    let steps = config.aliases.get(name)?;
+    let args = args.trim();
+    let substitutes = steps.iter().any(|step| step.contains("$*"));
+    let last = steps.len().saturating_sub(1);
+    Some(
+        steps
+            .iter()
+            .enumerate()
+            .map(|(i, step)| match (substitutes, i == last && !args.is_empty()) {
+                (true, _) => step.replace("$*", args),
+                (false, true) => format!("{} {}", step, args),
+                (false, false) => step.clone(),
+            })
+            .collect(),
+    )
+}
+
 // --- Helper Functions ---
 
 fn print_initial_status(config: &Config) {
@@ -160,7 +414,7 @@ fn print_initial_status(config: &Config) {
 fn format_prompt(config: &Config) -> String {
     let prompt_model = config.get_active_model_name();
     let prompt_provider = &config.active_provider;
-    format!("{}{}", format!("{}:{}", prompt_provider, prompt_model), "*")
+    format!("{}:{}*", prompt_provider, prompt_model)
 }
 
 fn is_exit_command(input: &str) -> bool {
@@ -168,109 +422,350 @@ fn is_exit_command(input: &str) -> bool {
 }
 
 // --- Shell Command Handler ---
-fn handle_shell_command(input: &str) -> Result<()> {
+// Returns whether the command ran and exited successfully
+fn handle_shell_command(input: &str) -> Result<bool> {
     let command_str = input[1..].trim();
     if command_str.is_empty() {
         eprintln!("{}", SHELL_COMMAND_USAGE);
-        return Ok(());
+        return Ok(false);
+    }
+    let output = shell::run_shell_command(command_str)?;
+    Ok(output.is_some_and(|o| o.status.success()))
+}
+
+// --- Captured Shell Command Handler ---
+// `!!cmd` and `/run cmd`: runs the command and keeps its output as context
+// for the next prompt. Returns whether output was captured; a non-zero exit
+// still counts, since failing output is often what the model should see.
+fn handle_shell_capture(command_str: &str, config: &Config, state: &mut SessionState) -> Result<bool> {
+    let command_str = command_str.trim();
+    if command_str.is_empty() {
+        eprintln!("{}", SHELL_CAPTURE_USAGE);
+        return Ok(false);
     }
-    println!("Executing: {}", command_str);
+    let Some(output) = shell::run_shell_command(command_str)? else {
+        return Ok(false);
+    };
+    state
+        .pending_context
+        .push(shell::format_shell_output(command_str, &output, config.attachment_max_bytes));
+    println!("Output captured; it will be sent with your next prompt.");
     println!("---");
-    let output_result = if cfg!(target_os = "windows") {
-        Command::new("cmd").args(["/C", command_str]).output()
+    Ok(true)
+}
+
+// --- LLM Prompt Handler ---
+// Returns false if the request failed (see `run_turn`)
+async fn handle_llm_prompt(
+    input: &str,
+    config: &Config,
+    client: &Client,
+    state: &mut SessionState,
+    retrieve: bool,
+    temperature: Option<f32>,
+) -> Result<bool> {
+    let expansion = attachments::expand_file_references(input, config)?;
+    for file in &expansion.files {
+        let note = if file.truncated { ", truncated" } else { "" };
+        println!("Attached: {} ({} bytes{})", file.path.display(), file.size, note);
+    }
+    let images: Vec<Image> = state
+        .pending_images
+        .iter()
+        .chain(&expansion.images)
+        .map(|(path, image)| {
+            println!("Attached image: {} ({})", path.display(), image.mime_type);
+            image.clone()
+        })
+        .collect();
+    for (reference, reason) in &expansion.skipped {
+        println!("Skipped @{}: {}", reference, reason);
+    }
+    let question = if retrieve {
+        retrieve_context(&expansion.prompt, config, client, state).await?
     } else {
-        Command::new("sh").arg("-c").arg(command_str).output()
+        expansion.prompt
     };
-    match output_result {
-        Ok(output) => {
-            if !output.stdout.is_empty() {
-                print!("{}", String::from_utf8_lossy(&output.stdout));
-            }
-            io::stdout().flush().context("Failed to flush stdout after command output")?;
-            if !output.stderr.is_empty() {
-                eprint!("{}", String::from_utf8_lossy(&output.stderr));
+    let prompt = if state.pending_context.is_empty() {
+        question
+    } else {
+        println!("Including output of {} captured command(s).", state.pending_context.len());
+        format!("{}\n{}", state.pending_context.join("\n"), question)
+    };
+    let turn_start = state.conversation.len();
+    let mut message = Message::user_with_images(prompt, images);
+    if message.content != input {
+        message.typed = Some(input.to_string());
+    }
+    state.conversation.begin_turn(message);
+    let answered = run_turn(config, client, state, turn_start, temperature).await?;
+    if answered {
+        state.pending_context.clear();
+        state.pending_images.clear();
+    }
+    Ok(answered)
+}
+
+// Answers the prompt at `turn_start`, running tool calls, agent commands and
+// schema retries as enabled. Returns false if a request failed, in which case
+// the turn has been dropped from the conversation (captured command output
+// and images stay pending for the next attempt).
+async fn run_turn(
+    config: &Config,
+    client: &Client,
+    state: &mut SessionState,
+    turn_start: usize,
+    temperature: Option<f32>,
+) -> Result<bool> {
+    // Without agent mode or tools this is a single request; otherwise the model
+    // may call tools or ask for commands, whose results are fed back until it
+    // gives a final answer.
+    // With a response schema, one extra step re-prompts an invalid reply.
+    let multi_step = state.agent_mode || state.tools_enabled;
+    let schema_retries = usize::from(state.response_schema.is_some());
+    let max_steps = if multi_step { config.agent_max_steps.max(1) } else { 1 } + schema_retries;
+    let mut schema_retried = false;
+    for _ in 0..max_steps {
+        let Some(generation) = send_conversation(config, client, state, temperature).await else {
+            // Drop the unfinished turn so the next one starts clean
+            state.conversation.truncate(turn_start);
+            return Ok(false);
+        };
+        if !generation.tool_calls.is_empty() {
+            for call in &generation.tool_calls {
+                println!("Tool call: {}({})", call.name, call.arguments);
+                let result = state.tools.execute(config, call).await;
+                debug!("Tool {} returned {} bytes", call.name, result.len());
+                state.conversation.push(Message::tool_result(call, result));
             }
-            io::stderr().flush().context("Failed to flush stderr after command output")?;
-            if !output.status.success() {
-                eprintln!("\nCommand exited with status: {}", output.status);
+            println!("---");
+            continue;
+        }
+        if let Some((_, schema)) = &state.response_schema {
+            match schema.validate(&generation.text) {
+                Ok(value) => {
+                    println!("{}", serde_json::to_string_pretty(&value).unwrap_or_default());
+                    println!("---");
+                }
+                Err(errors) if !schema_retried => {
+                    println!("Reply does not match the schema; asking again:");
+                    errors.iter().for_each(|e| println!("  - {}", e));
+                    println!("---");
+                    schema_retried = true;
+                    state.conversation.push(Message::user(structured::retry_prompt(&errors)));
+                    continue;
+                }
+                Err(errors) => {
+                    eprintln!("Reply still does not match the schema:");
+                    errors.iter().for_each(|e| eprintln!("  - {}", e));
+                    println!("---");
+                }
             }
+            return Ok(true);
         }
-        Err(e) => {
-            error!("Failed to execute command '{}': {}", command_str, e);
-            eprintln!("Error executing command: {}", e);
+        if !state.agent_mode {
+            return Ok(true);
         }
+        let Some(request) = agent::parse_command_request(&generation.text) else {
+            return Ok(true);
+        };
+        let feedback = handle_agent_command(config, &request.command)?;
+        state.conversation.push(Message::user(feedback));
     }
+    println!("Stopped after {} steps without a final answer.", max_steps);
     println!("---");
-    Ok(())
+    Ok(true)
 }
 
-// --- LLM Prompt Handler ---
-async fn handle_llm_prompt(input: &str, config: &Config, client: &Client) -> Result<()> {
-    println!("... generating via {} ...", config.active_provider);
-    let generation_result = match config.active_provider {
-        LlmProvider::Ollama => ollama::generate(client, config, None, input).await,
-        LlmProvider::Gemini => {
-            if config.gemini_api_key.is_none() {
-                Err(anyhow!("GEMINI_API_KEY not set."))
-            } else {
-                gemini::generate(client, config, input).await
-            }
-        }
-        LlmProvider::Groq => {
-            if config.groq_api_key.is_none() {
-                Err(anyhow!("GROQ_API_KEY not set."))
-            } else {
-                groq::generate(client, config, input).await
-            }
-        }
-        LlmProvider::HuggingFace => {
-                    if config.huggingface_api_key.is_none() {
-                        Err(anyhow!("HUGGINGFACE_API_KEY not set."))
-                    } else {
-                        huggingface::generate(client, config, input).await
-                    }
-                }
+// Sends the conversation (after context checks), renders and records the
+// answer, and appends it to the conversation. Errors are reported here and
+// the caller drops the unanswered turn.
+async fn send_conversation(
+    config: &Config,
+    client: &Client,
+    state: &mut SessionState,
+    temperature: Option<f32>,
+) -> Option<Generation> {
+    prepare_context(config, client, state).await;
+
+    let blocked = state.check_budget(config);
+    let mut messages = Vec::with_capacity(state.conversation.len() + 1);
+    if state.agent_mode {
+        messages.push(Message::system(agent::AGENT_SYSTEM_PROMPT));
+    }
+    messages.extend_from_slice(state.conversation.messages());
+
+    let tools = if state.tools_enabled { state.tools.specs() } else { Vec::new() };
+    let options = RequestOptions {
+        tools: &tools,
+        response_schema: state.response_schema.as_ref().map(|(_, schema)| &schema.schema),
+        temperature,
     };
 
+    println!("... generating via {} ...", config.active_provider);
+    let generation_result = llm::generate_with_fallback(client, config, &messages, &options, &blocked).await;
+
     // Display LLM result or error
     match generation_result {
-        Ok(response) => {
-            if let Ok(mut glow_process) = Command::new("glow")
-                .stdin(Stdio::piped())
-                .stdout(Stdio::inherit()) // Inherit glow's stdout to print to the terminal
-                .stderr(Stdio::inherit()) // Inherit glow's stderr for any errors
-                .spawn()
-            {
-                if let Some(mut stdin) = glow_process.stdin.take() {
-                    if let Err(e) = stdin.write_all(response.as_bytes()) {
-                        eprintln!("Error writing to glow's stdin: {}", e);
-                    }
-                }
-
-                if let Err(e) = glow_process.wait() {
-                    eprintln!("Error waiting for glow to finish: {}", e);
-                }
-            } else {
-                // If glow is not found or fails to start, fall back to plain text
-                println!("\n{}", response.trim());
+        Ok(generation) => {
+            // Structured replies are shown once validated
+            let show_text = !generation.text.trim().is_empty() || generation.tool_calls.is_empty();
+            if show_text && state.response_schema.is_none() {
+                render_response(&generation.text);
             }
+            if generation.provider != config.active_provider {
+                println!("[answered by {}:{} via fallback]", generation.provider, generation.model);
+            }
+            let request_cost = state.record_generation(config, &generation);
+            println!("[{}{}]", generation.usage, format_cost(request_cost));
+            // History keeps the prompt as typed, not attached files or output
+            if let Some(turn) = state.conversation.last_turn() {
+                let message = &state.conversation.messages()[turn];
+                let prompt = message.typed.as_deref().unwrap_or(&message.content);
+                state.record_exchange(config, prompt, &generation, temperature, request_cost);
+            }
+            let mut answer = Message::assistant_with_tool_calls(generation.text.clone(), generation.tool_calls.clone());
+            answer.meta = Some(AnswerMeta {
+                provider: generation.provider.clone(),
+                model: generation.model.clone(),
+                temperature,
+                usage: generation.usage.clone(),
+            });
+            state.conversation.push(answer);
             println!("---");
+            Some(generation)
         }
         Err(e) => {
             error!("Generation error [{}]: {:?}", config.active_provider, e);
             eprintln!("\nError [{}]: {}", config.active_provider, e);
             println!("---");
+            None
         }
     }
-    Ok(())
+}
+
+// --- Agent Command Approval ---
+// Asks for approval, runs the approved command and returns the message to
+// send back.
+fn handle_agent_command(config: &Config, requested: &str) -> Result<String> {
+    let command = match agent::approve_command(config, requested)? {
+        Approval::Run(command) => command,
+        Approval::Refused(message) => return Ok(message),
+    };
+    Ok(match shell::run_shell_command(&command)? {
+        Some(output) => shell::format_shell_output(&command, &output, config.attachment_max_bytes),
+        None => format!("The command `{}` could not be started.", command),
+    })
+}
+
+// --- Context Window Check ---
+// Warns when the conversation approaches the model's context window and
+// trims (or summarizes) it according to the configured strategy.
+async fn prepare_context(config: &Config, client: &Client, state: &mut SessionState) {
+    let tokenizer = Tokenizer::for_model(config.get_active_model_name());
+    let window = state.context_window(config, client).await;
+    let available = window.saturating_sub(config.context_reserve_tokens) as usize;
+    let mut tokens = tokenizer.count_messages(state.conversation.messages());
+    let approx = if tokenizer.is_exact() { "" } else { "~" };
+
+    if config.context_strategy == ContextStrategy::Summarize
+        && tokens as f32 >= available as f32 * config.summarize_fraction
+    {
+        println!("... summarizing older messages ({}{} of {} tokens) ...", approx, tokens, available);
+        match compact_conversation(config, client, state).await {
+            Ok(Some(_)) => tokens = tokenizer.count_messages(state.conversation.messages()),
+            Ok(None) => {}
+            // Fall through to plain trimming below if it still does not fit
+            Err(e) => eprintln!("Warning: summarization failed: {:#}", e),
+        }
+    }
+
+    if tokens > available {
+        match config.context_strategy {
+            ContextStrategy::WarnOnly => println!(
+                "Warning: conversation is {}{} tokens, over the {} available in the {}-token context window.",
+                approx, tokens, available, window
+            ),
+            strategy => {
+                let dropped = state
+                    .conversation
+                    .rewrite(|messages| context::trim_to_fit(messages, &tokenizer, available, strategy));
+                let remaining = tokenizer.count_messages(state.conversation.messages());
+                println!(
+                    "Trimmed {} old messages ({}) to fit the {}-token context window (now {}{} tokens).",
+                    dropped, strategy, window, approx, remaining
+                );
+            }
+        }
+    } else if config.context_strategy != ContextStrategy::Summarize
+        && tokens as f32 >= available as f32 * config.context_warn_fraction
+    {
+        println!(
+            "Warning: conversation is {}{} of {} available tokens; old turns will be trimmed soon.",
+            approx, tokens, available
+        );
+    }
+}
+
+// Summarizes older turns in place and accounts for the summary request.
+// Returns the number of messages that were summarized.
+async fn compact_conversation(config: &Config, client: &Client, state: &mut SessionState) -> Result<Option<usize>> {
+    let blocked = cost::blocked_providers(config, &mut state.ledger);
+    let Some(compaction) = summary::compact(client, config, &mut state.conversation, &blocked).await? else {
+        return Ok(None);
+    };
+    let request_cost = state.record_generation(config, &compaction.generation);
+    println!(
+        "Summarized {} messages via {}:{} [{}{}]",
+        compaction.summarized_messages,
+        compaction.generation.provider,
+        compaction.generation.model,
+        compaction.generation.usage,
+        format_cost(request_cost)
+    );
+    Ok(Some(compaction.summarized_messages))
+}
+
+pub(crate) fn format_cost(request_cost: Option<f64>) -> String {
+    match request_cost {
+        Some(c) if c > 0.0 => format!(" | ${:.6}", c),
+        _ => String::new(),
+    }
+}
+
+// --- Response Rendering ---
+// Renders markdown through `glow` when available, plain text otherwise.
+pub(crate) fn render_response(response: &str) {
+    if let Ok(mut glow_process) = Command::new("glow")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::inherit()) // Inherit glow's stdout to print to the terminal
+        .stderr(Stdio::inherit()) // Inherit glow's stderr for any errors
+        .spawn()
+    {
+        if let Some(mut stdin) = glow_process.stdin.take() {
+            if let Err(e) = stdin.write_all(response.as_bytes()) {
+                eprintln!("Error writing to glow's stdin: {}", e);
+            }
+        }
+
+        if let Err(e) = glow_process.wait() {
+            eprintln!("Error waiting for glow to finish: {}", e);
+        }
+    } else {
+        // If glow is not found or fails to start, fall back to plain text
+        println!("\n{}", response.trim());
+    }
 }
 
 // --- Application Command Handler ---
+// Returns false for unknown commands and for commands that reported a
+// failure without an error (a failed request, an unusable provider)
 async fn handle_app_command(
     input: &str,
     config: &mut Config,
     client: &Client,
-) -> Result<()> {
+    state: &mut SessionState,
+) -> Result<bool> {
     let parts: Vec<&str> = input[1..].splitn(2, ' ').collect();
     let command = parts[0].trim();
     let args_str = parts.get(1).map(|s| s.trim()).unwrap_or("");
@@ -278,13 +773,71 @@ async fn handle_app_command(
 
     debug!("Handling app command: '{}', args: {:?}", command, args);
 
+    let mut succeeded = true;
     match command {
-        "help" => print_help(),
+        "help" => print_help(config),
         "status" => handle_status_command(config, client).await?,
-        "use" => handle_use_command(config, &args)?,
-        "model" => handle_model_command(config, client, args_str).await?,
-        "model_list" => handle_model_list_command(config, client).await?,
-        "select_model" => handle_select_model_command(config, client, args_str).await?,
+        "use" => succeeded = handle_use_command(config, &args)?,
+        "model" => handle_model_command(config, client, args_str, &state.model_cache).await?,
+        "model_list" => handle_model_list_command(config, client, &state.model_cache).await?,
+        "select_model" => handle_select_model_command(config, client, args_str, &state.model_cache).await?,
+        "compare" => compare::handle_compare_command(config, client, state, args_str).await?,
+        "usage" => handle_usage_command(state),
+        "cost" => handle_cost_command(config, state),
+        "context" => handle_context_command(config, client, state, &args).await?,
+        "compact" => {
+            if compact_conversation(config, client, state).await?.is_none() {
+                println!("Nothing to compact yet.");
+            }
+            println!("---");
+        }
+        "run" => succeeded = handle_shell_capture(args_str, config, state)?,
+        "image" => handle_image_command(config, state, args_str),
+        "schema" => handle_schema_command(state, args_str),
+        "code" => handle_code_command(state, &args, args_str)?,
+        "t" => succeeded = handle_template_command(config, client, state, args_str).await?,
+        "editor" => match shell::compose_in_editor(args_str)? {
+            Some(prompt) => {
+                let retrieve = state.rag_always;
+                succeeded = handle_llm_prompt(&prompt, config, client, state, retrieve, None).await?;
+            }
+            None => {
+                println!("Empty prompt; nothing sent.");
+                println!("---");
+                succeeded = false;
+            }
+        },
+        "session" => handle_session_command(state, &args)?,
+        "export" => handle_export_command(config, state, &args, args_str)?,
+        "import" => handle_import_command(state, args_str)?,
+        "search" => handle_search_command(state, args_str)?,
+        "show" => handle_show_command(state, &args)?,
+        "retry" => succeeded = handle_retry_command(config, client, state, &args).await?,
+        "edit" => succeeded = handle_edit_command(config, client, state, args_str).await?,
+        "undo" => handle_undo_command(state),
+        "branches" => handle_branches_command(state),
+        "checkout" => handle_checkout_command(state, &args),
+        "embed" => handle_embed_command(config, client, args_str).await?,
+        "index" => handle_index_command(config, client, state, args_str).await?,
+        "ask" => {
+            if args_str.is_empty() {
+                println!("Usage: /ask <question>");
+                println!("---");
+                succeeded = false;
+            } else {
+                succeeded = handle_llm_prompt(args_str, config, client, state, true, None).await?;
+            }
+        }
+        "agent" => handle_agent_toggle_command(config, state, &args),
+        "tools" => handle_tools_command(config, state, &args),
+        "mcp" => handle_mcp_command(config, state, &args),
+        "clear" => {
+            state.conversation.clear();
+            state.pending_context.clear();
+            state.pending_images.clear();
+            println!("Conversation cleared.");
+            println!("---");
+        }
         "gemini_config" => handle_gemini_config_command(config, &args)?,
         "groq_config" => handle_groq_config_command(config, &args)?,
         "huggingface_config" => handle_huggingface_config_command(config, &args)?,
@@ -293,10 +846,11 @@ async fn handle_app_command(
         _ => {
             println!("{} {}", UNKNOWN_COMMAND_MSG, command);
             println!("---");
+            succeeded = false;
         }
     }
 
-    Ok(())
+    Ok(succeeded)
 }
 
 // --- Command-Specific Handlers ---
@@ -342,7 +896,8 @@ async fn handle_status_command(config: &Config, client: &Client) -> Result<()> {
     Ok(())
 }
 
-fn handle_use_command(config: &mut Config, args: &[&str]) -> Result<()> {
+// Returns whether the requested provider is now active
+fn handle_use_command(config: &mut Config, args: &[&str]) -> Result<bool> {
     if args.len() != 1 {
         println!("Usage: /use <provider> (ollama, gemini, groq)");
     } else {
@@ -384,23 +939,18 @@ fn handle_use_command(config: &mut Config, args: &[&str]) -> Result<()> {
         }
     }
     println!("---");
-    Ok(())
+    let requested = args.first().and_then(|name| LlmProvider::from_config_name(name));
+    Ok(args.len() == 1 && requested.as_ref() == Some(&config.active_provider))
 }
 
-async fn handle_model_command(config: &mut Config, client: &Client, args_str: &str) -> Result<()> {
+async fn handle_model_command(config: &mut Config, client: &Client, args_str: &str, cache: &ModelCache) -> Result<()> {
     if args_str.is_empty() {
         println!("Current model: {}", config.get_active_model_name());
         println!("Usage: /model <name>");
         println!("Use /select_model for interactive selection.");
     } else {
         let model_name = args_str;
-        let known_models = match config.active_provider {
-            LlmProvider::Ollama => ollama::list_models(client, config).await.ok(),
-            LlmProvider::Gemini => gemini::list_models(client, config).await.ok(),
-            LlmProvider::Groq => groq::list_models(client, config).await.ok(),
-            LlmProvider::HuggingFace => huggingface::list_models(client, config).await.ok(),
-
-        };
+        let known_models = fetch_models(client, config, cache).await.ok();
         if let Some(models) = known_models {
             if !models.iter().any(|m| m == model_name) {
                 warn!(
@@ -421,11 +971,11 @@ async fn handle_model_command(config: &mut Config, client: &Client, args_str: &s
     Ok(())
 }
 
-async fn handle_model_list_command(config: &Config, client: &Client) -> Result<()> {
+async fn handle_model_list_command(config: &Config, client: &Client, cache: &ModelCache) -> Result<()> {
     match config.active_provider {
         LlmProvider::Ollama => {
             println!("Fetching available Ollama models...");
-            match ollama::list_models(client, config).await {
+            match fetch_models(client, config, cache).await {
                 Ok(models) => {
                     if models.is_empty() {
                         println!("No Ollama models found.");
@@ -441,20 +991,25 @@ async fn handle_model_list_command(config: &Config, client: &Client) -> Result<(
             }
         }
         LlmProvider::Gemini => {
-            handle_gemini_list_command(config, client).await?;
+            handle_gemini_list_command(config, client, cache).await?;
         }
         LlmProvider::Groq => {
-            handle_groq_list_command(config, client).await?;
+            handle_groq_list_command(config, client, cache).await?;
         }
         LlmProvider::HuggingFace => {
-            handle_huggingface_list_command(config, client).await?;
+            handle_huggingface_list_command(config, client, cache).await?;
         }        
     }
     println!("---");
     Ok(())
 }
 
-async fn handle_select_model_command(config: &mut Config, client: &Client, args_str: &str) -> Result<()> {
+async fn handle_select_model_command(
+    config: &mut Config,
+    client: &Client,
+    args_str: &str,
+    cache: &ModelCache,
+) -> Result<()> {
     if !args_str.is_empty() {
         println!("Usage: /select_model");
         println!("---");
@@ -463,19 +1018,19 @@ async fn handle_select_model_command(config: &mut Config, client: &Client, args_
     let models = match config.active_provider {
         LlmProvider::Ollama => {
             println!("Fetching available Ollama models...");
-            ollama::list_models(client, config).await
+            fetch_models(client, config, cache).await
         }
         LlmProvider::Gemini => {
             println!("Fetching available Gemini models for selection...");
-            gemini::list_models(client, config).await
+            fetch_models(client, config, cache).await
         }
         LlmProvider::Groq => {
             println!("Fetching available Groq models for selection...");
-            groq::list_models(client, config).await
+            fetch_models(client, config, cache).await
         }
         LlmProvider::HuggingFace => {
             println!("Fetching available Hugging Face models for selection...");
-            huggingface::list_models(client, config).await
+            fetch_models(client, config, cache).await
             }
     };
     match models {
@@ -502,14 +1057,28 @@ async fn handle_select_model_command(config: &mut Config, client: &Client, args_
     Ok(())
 }
 
-async fn handle_gemini_list_command(config: &Config, client: &Client) -> Result<()> {
+// Fetches the active provider's models and refreshes the cached list that
+// feeds /model completion
+async fn fetch_models(client: &Client, config: &Config, cache: &ModelCache) -> Result<Vec<String>> {
+    let provider = &config.active_provider;
+    let models = match provider {
+        LlmProvider::Ollama => ollama::list_models(client, config).await?,
+        LlmProvider::Gemini => gemini::list_models(client, config).await?,
+        LlmProvider::Groq => groq::list_models(client, config).await?,
+        LlmProvider::HuggingFace => huggingface::list_models(client, config).await?,
+    };
+    cache.store(provider, &models);
+    Ok(models)
+}
+
+async fn handle_gemini_list_command(config: &Config, client: &Client, cache: &ModelCache) -> Result<()> {
     if config.gemini_api_key.is_none() {
         println!("Error: GEMINI_API_KEY not set.");
         println!("---");
         return Ok(());
     }
     println!("Fetching available Gemini models (requires valid API key)...");
-    match gemini::list_models(client, config).await {
+    match fetch_models(client, config, cache).await {
         Ok(models) => {
             if models.is_empty() {
                 println!("No Gemini models supporting chat found (or API key invalid/restricted?).");
@@ -625,14 +1194,14 @@ fn handle_gemini_config_command(config: &mut Config, args: &[&str]) -> Result<()
     Ok(())
 }
 
-async fn handle_groq_list_command(config: &Config, client: &Client) -> Result<()> {
+async fn handle_groq_list_command(config: &Config, client: &Client, cache: &ModelCache) -> Result<()> {
     if config.groq_api_key.is_none() {
         println!("Error: GROQ_API_KEY not set.");
         println!("---");
         return Ok(());
     }
     println!("Fetching available Groq models...");
-    match groq::list_models(client, config).await {
+    match fetch_models(client, config, cache).await {
         Ok(models) => {
             if models.is_empty() {
                 println!("No Groq models found.");
@@ -650,16 +1219,30 @@ async fn handle_groq_list_command(config: &Config, client: &Client) -> Result<()
     Ok(())
 }
 
-async fn handle_huggingface_list_command(config: &Config, _client: &Client) -> Result<()> {
+async fn handle_huggingface_list_command(config: &Config, client: &Client, cache: &ModelCache) -> Result<()> {
     if config.huggingface_api_key.is_none() {
         println!("Error: HUGGINGFACE_API_KEY not set.");
         println!("---");
-         return Ok(());
-     }
-    println!("Hugging Face model listing is not implemented yet.");
+        return Ok(());
+    }
+    println!("Fetching available Hugging Face models...");
+    match fetch_models(client, config, cache).await {
+        Ok(models) => {
+            if models.is_empty() {
+                println!("No Hugging Face models found (listing is not supported by the API yet).");
+            } else {
+                println!("Available Hugging Face models:");
+                models.iter().for_each(|m| println!(" - {}", m));
+            }
+        }
+        Err(e) => {
+            error!("Failed to list Hugging Face models: {:?}", e);
+            eprintln!("Error fetching Hugging Face models: {}", e);
+        }
+    }
     println!("---");
     Ok(())
-    }
+}
     
     fn handle_huggingface_config_command(_config: &mut Config, _args: &[&str]) -> Result<()> {
     println!("Hugging Face configuration is not implemented yet.");
@@ -671,12 +1254,830 @@ async fn handle_huggingface_list_command(config: &Config, _client: &Client) -> R
 fn handle_groq_config_command(_config: &mut Config, args: &[&str]) -> Result<()> {
     if args.is_empty() {
         println!("Current Groq Configuration:");
+    }
+    println!("---");
+    Ok(())
+}
+
+fn handle_agent_toggle_command(config: &Config, state: &mut SessionState, args: &[&str]) {
+    match args {
+        [] => {
+            println!("Agent mode: {}", if state.agent_mode { "on" } else { "off" });
+            println!("  Max steps:  {}", config.agent_max_steps);
+            println!("  Allow list: {:?}", config.agent_allow_commands);
+            println!("  Deny list:  {:?}", config.agent_deny_commands);
+            println!("Usage: /agent [on|off]");
+        }
+        ["on"] => {
+            state.agent_mode = true;
+            println!("Agent mode on: the model may request shell commands for your approval.");
+        }
+        ["off"] => {
+            state.agent_mode = false;
+            println!("Agent mode off.");
+        }
+        _ => println!("Usage: /agent [on|off]"),
+    }
+    println!("---");
+}
+
+// Queues an image to be sent with the next prompt
+fn handle_image_command(config: &Config, state: &mut SessionState, args_str: &str) {
+    let path_str = args_str.trim();
+    if path_str.is_empty() {
+        println!("Usage: /image <path>   (or @img:<path> inside a prompt)");
     } else {
+        let path = attachments::expand_home(path_str);
+        match attachments::load_image(&path, config.image_max_bytes) {
+            Ok(image) => {
+                println!("Image queued: {} ({}); it will be sent with your next prompt.", path.display(), image.mime_type);
+                state.pending_images.push((path, image));
+            }
+            Err(e) => eprintln!("Cannot attach {}: {:#}", path.display(), e),
+        }
+    }
+    println!("---");
+}
+
+// Same options as `llm-chat embed`; the words that are not options form one text
+async fn handle_embed_command(config: &Config, client: &Client, args_str: &str) -> Result<()> {
+    let words = std::iter::once("embed").chain(args_str.split_whitespace());
+    let mut args = match EmbedArgs::try_parse_from(words) {
+        Ok(args) => args,
+        Err(e) => {
+            println!("{}", e.render());
+            println!("---");
+            return Ok(());
+        }
+    };
+    if args.texts.len() > 1 {
+        args.texts = vec![args.texts.join(" ")];
+    }
+    let (count, dims) = embed::run_embed(config, client, &args, false).await?;
+    if let Some(path) = &args.output {
+        println!("Wrote {} embedding(s) ({} dimensions) to {}.", count, dims, path.display());
+    }
+    println!("---");
+    Ok(())
+}
+
+// --- Retrieval ---
+// `/index <dir>` builds or refreshes the index; without a directory it shows
+// the current one.
+async fn handle_index_command(config: &Config, client: &Client, state: &mut SessionState, args_str: &str) -> Result<()> {
+    if args_str.is_empty() {
+        match &state.rag_index {
+            Some(index) => println!(
+                "Index: {} ({} files, {} chunks, {}:{}){}",
+                index.root.display(),
+                index.file_count(),
+                index.chunk_count(),
+                index.provider,
+                index.model,
+                if state.rag_always { ", used for every prompt" } else { "" }
+            ),
+            None => println!("No index loaded. Usage: /index <dir>"),
+        }
+        println!("---");
+        return Ok(());
     }
+    build_index(config, client, state, &attachments::expand_home(trailing_path(args_str))).await
+}
+
+async fn build_index(config: &Config, client: &Client, state: &mut SessionState, dir: &Path) -> Result<()> {
+    println!("Indexing {} with {}...", dir.display(), config.embedding_provider());
+    let (index, stats) = Index::build(client, config, dir).await?;
+    println!(
+        "Indexed {}: {} file(s) embedded, {} unchanged, {} removed; {} chunks.",
+        index.root.display(),
+        stats.embedded_files,
+        stats.unchanged_files,
+        stats.removed_files,
+        stats.chunks
+    );
     println!("---");
+    state.rag_index = Some(index);
     Ok(())
 }
 
+// Finds the chunks closest to the question and returns the question with
+// them prepended as numbered, citable excerpts
+async fn retrieve_context(question: &str, config: &Config, client: &Client, state: &SessionState) -> Result<String> {
+    let index = state.rag_index.as_ref().ok_or_else(|| anyhow!("No index loaded; run /index <dir> first."))?;
+    let hits = index.search(client, config, question, config.rag_top_k).await?;
+    for hit in &hits {
+        println!("Retrieved: {}:{}-{} ({:.3})", hit.path, hit.chunk.start_line, hit.chunk.end_line, hit.score);
+    }
+    Ok(rag::augment_prompt(question, &hits))
+}
+
+fn handle_schema_command(state: &mut SessionState, args_str: &str) {
+    match args_str.trim() {
+        "" => match &state.response_schema {
+            Some((path, _)) => println!("Replies must match the JSON schema in {}.", path.display()),
+            None => println!("No response schema set. Usage: /schema <schema.json> | /schema off"),
+        },
+        "off" => {
+            state.response_schema = None;
+            println!("Response schema cleared.");
+        }
+        path_str => {
+            let path = attachments::expand_home(path_str);
+            match ResponseSchema::load(&path) {
+                Ok(schema) => {
+                    println!("Replies must now match the JSON schema in {}.", path.display());
+                    state.response_schema = Some((path, schema));
+                }
+                Err(e) => eprintln!("Cannot use {}: {:#}", path.display(), e),
+            }
+        }
+    }
+    println!("---");
+}
+
+// --- Saved Sessions ---
+fn handle_session_command(state: &mut SessionState, args: &[&str]) -> Result<()> {
+    match args {
+        ["save", name] => {
+            let path = state.conversation.save_session(name)?;
+            println!("Saved session '{}' to {}.", name, path.display());
+        }
+        ["load", name] => {
+            let loaded = Conversation::load_session(name)?;
+            if !confirm_replace(state)? {
+                println!("Not loaded.");
+                println!("---");
+                return Ok(());
+            }
+            state.conversation = loaded;
+            println!(
+                "Loaded session '{}' ({} message(s), {} branch(es)).",
+                name,
+                state.conversation.len(),
+                state.conversation.branches().len()
+            );
+        }
+        [] | ["list"] => {
+            let names = conversation::session_names();
+            if names.is_empty() {
+                println!("No saved sessions.");
+            } else {
+                println!("Saved sessions:");
+                names.iter().for_each(|name| println!("  {}", name));
+            }
+        }
+        _ => println!("Usage: /session save <name> | /session load <name> | /session list"),
+    }
+    println!("---");
+    Ok(())
+}
+
+// --- Export ---
+// Writes the active branch with session metadata for sharing.
+fn handle_export_command(config: &Config, state: &SessionState, args: &[&str], args_str: &str) -> Result<()> {
+    let path = Some(trailing_path(skip_words(args_str, 1)))
+        .filter(|path| !path.is_empty())
+        .map(attachments::expand_home);
+    let Some(format) = args.first().and_then(|format| ExportFormat::from_name(format)) else {
+        println!("Usage: /export md|html|json [path]");
+        println!("---");
+        return Ok(());
+    };
+    if state.conversation.is_empty() {
+        println!("Nothing to export yet.");
+        println!("---");
+        return Ok(());
+    }
+    // Only the numeric generation settings, not URLs and model names
+    let parameters = config
+        .get_provider_config(&config.active_provider)
+        .into_iter()
+        .filter(|(_, _, float, int, _)| float.is_some() || int.is_some())
+        .map(|(name, value, _, _, _)| (name.to_string(), value))
+        .collect();
+    let info = ExportInfo {
+        provider: &config.active_provider,
+        model: config.get_active_model_name(),
+        parameters,
+        usage: &state.usage,
+        cost: state.cost,
+        branch: state.conversation.active(),
+        exported_at: chrono::Local::now(),
+    };
+    let path = export::output_path(format, path.as_deref(), &info);
+    if path.exists() && !confirm(&format!("{} exists. Overwrite?", path.display()))? {
+        println!("Not exported.");
+    } else {
+        export::write(format, &path, state.conversation.messages(), &info)?;
+        println!("Exported {} message(s) to {}.", state.conversation.len(), path.display());
+    }
+    println!("---");
+    Ok(())
+}
+
+// --- Import ---
+// Loads another tool's export as a new saved session. Files holding several
+// conversations need a number or part of a title to pick one.
+fn handle_import_command(state: &mut SessionState, args_str: &str) -> Result<()> {
+    let (path, selector) = split_path_argument(args_str);
+    if path.is_empty() {
+        println!("Usage: /import <file> [conversation number or title]");
+        println!("---");
+        return Ok(());
+    }
+    let path = attachments::expand_home(path);
+    let mut conversations = import::read_file(&path)?;
+    let index = match selector {
+        None if conversations.len() == 1 => 0,
+        None => {
+            println!("{} contains {} conversations:", path.display(), conversations.len());
+            for (i, imported) in conversations.iter().enumerate() {
+                let title = imported.title.as_deref().unwrap_or("(untitled)");
+                println!("  {:>3}. {} ({} message(s))", i + 1, title, imported.messages.len());
+            }
+            println!("Pick one with /import {} <number or title>", path.display());
+            println!("---");
+            return Ok(());
+        }
+        Some(selector) => match selector.parse::<usize>() {
+            Ok(n) if (1..=conversations.len()).contains(&n) => n - 1,
+            Ok(n) => return Err(anyhow!("No conversation {} (the file has {})", n, conversations.len())),
+            Err(_) => {
+                let needle = selector.to_lowercase();
+                conversations
+                    .iter()
+                    .position(|c| c.title.as_ref().is_some_and(|t| t.to_lowercase().contains(&needle)))
+                    .ok_or_else(|| anyhow!("No conversation titled like '{}'", selector))?
+            }
+        },
+    };
+    let imported = conversations.swap_remove(index);
+    let base = imported
+        .title
+        .clone()
+        .unwrap_or_else(|| path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default());
+    let name = conversation::unique_session_name(&base);
+    let count = imported.messages.len();
+    let conversation = Conversation::from_messages(imported.messages);
+    conversation.save_session(&name)?;
+    println!("Imported {} message(s) ({}) as session '{}'.", count, imported.format, name);
+    if confirm_replace(state)? {
+        state.conversation = conversation;
+        println!("Continue with any provider; /use and /model switch the one that answers.");
+    } else {
+        println!("Kept the current conversation; switch with /session load {}.", name);
+    }
+    println!("---");
+    Ok(())
+}
+
+// Splits "<path> [rest]" where the path may contain spaces: either quoted, or
+// the longest leading part that names an existing file
+fn split_path_argument(args_str: &str) -> (&str, Option<&str>) {
+    fn rest(rest: &str) -> Option<&str> {
+        Some(rest.trim()).filter(|r| !r.is_empty())
+    }
+    for quote in ['"', '\''] {
+        if let Some((path, after)) = args_str.strip_prefix(quote).and_then(|a| a.split_once(quote)) {
+            return (path, rest(after));
+        }
+    }
+    let mut ends: Vec<usize> = args_str.char_indices().filter(|(_, c)| c.is_whitespace()).map(|(i, _)| i).collect();
+    ends.push(args_str.len());
+    let end = ends
+        .iter()
+        .rev()
+        .copied()
+        .find(|&end| attachments::expand_home(&args_str[..end]).is_file())
+        .unwrap_or(ends[0]);
+    (&args_str[..end], rest(&args_str[end..]))
+}
+
+// A path given as the last argument: the rest of the line, optionally quoted
+fn trailing_path(args_str: &str) -> &str {
+    let args_str = args_str.trim();
+    ['"', '\'']
+        .iter()
+        .find_map(|&quote| args_str.strip_prefix(quote).and_then(|a| a.strip_suffix(quote)))
+        .unwrap_or(args_str)
+}
+
+// What follows the first `n` words of `args_str`
+fn skip_words(args_str: &str, n: usize) -> &str {
+    let mut rest = args_str.trim_start();
+    for _ in 0..n {
+        rest = rest.find(char::is_whitespace).map_or("", |i| rest[i..].trim_start());
+    }
+    rest
+}
+
+// Asks before a load or import replaces a non-empty conversation
+fn confirm_replace(state: &SessionState) -> Result<bool> {
+    if state.conversation.is_empty() {
+        return Ok(true);
+    }
+    confirm(&format!(
+        "Replace the current conversation ({} message(s); save it first with /session save)?",
+        state.conversation.len()
+    ))
+}
+
+// --- History Search ---
+// Past exchanges from every session, stored in the history database.
+const SEARCH_LIMIT: usize = 10;
+
+fn open_history(state: &SessionState) -> Result<&History> {
+    state.history.as_ref().ok_or_else(|| anyhow!("History database {:?} is unavailable", history::history_path()))
+}
+
+fn handle_search_command(state: &SessionState, terms: &str) -> Result<()> {
+    if terms.is_empty() {
+        println!("Usage: /search <terms>");
+        println!("---");
+        return Ok(());
+    }
+    let hits = open_history(state)?.search(terms, SEARCH_LIMIT)?;
+    if hits.is_empty() {
+        println!("No past exchanges match '{}'.", terms);
+    }
+    for hit in &hits {
+        let exchange = &hit.exchange;
+        println!("#{:<5} {}  {}", exchange.id, exchange.timestamp.format("%Y-%m-%d %H:%M"), exchange.target());
+        println!("  Q: {}", exchange.title(80));
+        println!("  {}", hit.snippet.split_whitespace().collect::<Vec<_>>().join(" "));
+    }
+    if !hits.is_empty() {
+        println!("Use /show <id> to display an exchange.");
+    }
+    println!("---");
+    Ok(())
+}
+
+fn handle_show_command(state: &SessionState, args: &[&str]) -> Result<()> {
+    let Some(id) = args.first().and_then(|id| id.trim_start_matches('#').parse::<i64>().ok()) else {
+        println!("Usage: /show <id>");
+        println!("---");
+        return Ok(());
+    };
+    let Some(exchange) = open_history(state)?.get(id)? else {
+        println!("No exchange #{} in the history.", id);
+        println!("---");
+        return Ok(());
+    };
+    let temperature = exchange.temperature.map_or_else(String::new, |t| format!(" | temperature {}", t));
+    println!("#{} {}  {}{}", exchange.id, exchange.timestamp.format("%Y-%m-%d %H:%M:%S"), exchange.target(), temperature);
+    println!("[{}]", exchange.summary_line());
+    println!("\nPrompt:\n{}", exchange.prompt.trim());
+    println!("\nAnswer:");
+    render_response(&exchange.response);
+    println!("---");
+    Ok(())
+}
+
+// --- Retry, Edit and Branches ---
+// /retry and /edit never overwrite an answer: they fork a new branch from the
+// last prompt, and /checkout switches back.
+async fn handle_retry_command(config: &Config, client: &Client, state: &mut SessionState, args: &[&str]) -> Result<bool> {
+    let mut retry_config = config.clone();
+    let mut temperature = None;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match *arg {
+            "--temperature" | "-t" => {
+                let value = rest.next().ok_or_else(|| anyhow!("{}", RETRY_USAGE))?;
+                temperature = Some(value.parse::<f32>().map_err(|_| anyhow!("Invalid temperature '{}'", value))?);
+            }
+            spec => {
+                let target = compare::parse_target(spec, config)?;
+                retry_config.active_provider = target.provider.clone();
+                retry_config.set_provider_model(&target.provider, target.model);
+            }
+        }
+    }
+    let Some(turn_start) = state.conversation.last_turn() else {
+        println!("Nothing to retry.");
+        println!("---");
+        return Ok(false);
+    };
+    let branch = state.conversation.fork(turn_start + 1);
+    println!("Retrying the last prompt on branch {}.", branch);
+    let answered = run_turn(&retry_config, client, state, turn_start, temperature).await?;
+    if !answered {
+        state.conversation.abandon_fork();
+    }
+    Ok(answered)
+}
+
+// `/edit` opens the last prompt in a line editor; `/edit <text>` replaces it
+// directly. Either way the new prompt is sent on a new branch.
+async fn handle_edit_command(config: &Config, client: &Client, state: &mut SessionState, args_str: &str) -> Result<bool> {
+    let Some(turn_start) = state.conversation.last_turn() else {
+        println!("No prompt to edit.");
+        println!("---");
+        return Ok(false);
+    };
+    let original = state.conversation.messages()[turn_start].clone();
+    let text = if args_str.is_empty() {
+        let mut editor = DefaultEditor::new()?;
+        match editor.readline_with_initial("edit> ", (&original.content, "")) {
+            Ok(text) => text,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => String::new(),
+            Err(e) => return Err(e.into()),
+        }
+    } else {
+        args_str.to_string()
+    };
+    let text = text.trim();
+    if text.is_empty() || text == original.content.trim() {
+        println!("Prompt unchanged; nothing sent.");
+        println!("---");
+        return Ok(false);
+    }
+    let branch = state.conversation.fork(turn_start);
+    println!("Sending the edited prompt on branch {}.", branch);
+    state.conversation.begin_turn(Message::user_with_images(text, original.images));
+    let answered = run_turn(config, client, state, turn_start, None).await?;
+    if !answered {
+        state.conversation.abandon_fork();
+    }
+    Ok(answered)
+}
+
+fn handle_undo_command(state: &mut SessionState) {
+    match state.conversation.last_turn() {
+        Some(turn_start) => {
+            let dropped = state.conversation.len() - turn_start;
+            state.conversation.truncate(turn_start);
+            println!("Dropped the last exchange ({} message(s)).", dropped);
+        }
+        None => println!("Nothing to undo."),
+    }
+    println!("---");
+}
+
+fn handle_branches_command(state: &SessionState) {
+    println!("Branches (* = active):");
+    print_branch(state, 0, 0);
+    println!("---");
+}
+
+// Prints a branch and, indented below it, the branches forked from it
+fn print_branch(state: &SessionState, id: usize, depth: usize) {
+    let branches = state.conversation.branches();
+    let branch = &branches[id];
+    let marker = if id == state.conversation.active() { "*" } else { " " };
+    let origin = branch.parent().map_or_else(String::new, |(parent, shared)| {
+        format!(", forked from {} after {} message(s)", parent, shared)
+    });
+    let prompt = branch.last_prompt().and_then(|p| p.lines().next()).unwrap_or("(empty)");
+    let prompt: String = if prompt.chars().count() > 60 {
+        format!("{}...", prompt.chars().take(60).collect::<String>())
+    } else {
+        prompt.to_string()
+    };
+    println!("{} {}{}: {} turn(s){} | {}", marker, "  ".repeat(depth), id, branch.turn_count(), origin, prompt);
+    for (child, _) in branches.iter().enumerate().filter(|(_, b)| b.parent().map(|(p, _)| p) == Some(id)) {
+        print_branch(state, child, depth + 1);
+    }
+}
+
+fn handle_checkout_command(state: &mut SessionState, args: &[&str]) {
+    match args {
+        [id] => match id.parse::<usize>() {
+            Ok(id) if state.conversation.checkout(id) => println!(
+                "Switched to branch {} ({} message(s)).",
+                id,
+                state.conversation.len()
+            ),
+            _ => println!("No branch '{}'; see /branches.", id),
+        },
+        _ => println!("Usage: /checkout <branch>"),
+    }
+    println!("---");
+}
+
+// --- Templates ---
+// `/t` lists the templates; `/t <name> key=value ...` fills one in, asking
+// for any variable that has neither a value nor a default, and sends it.
+async fn handle_template_command(config: &Config, client: &Client, state: &mut SessionState, args_str: &str) -> Result<bool> {
+    let (name, assignments) = args_str.split_once(char::is_whitespace).unwrap_or((args_str, ""));
+    if name.is_empty() {
+        println!("Templates in {}:", templates::templates_dir().display());
+        for name in templates::list_names() {
+            match Template::load(&name) {
+                Ok(template) => println!("  {:<20} {}", name, template.description.as_deref().unwrap_or("")),
+                Err(e) => println!("  {:<20} (invalid: {:#})", name, e),
+            }
+        }
+        println!("Usage: /t <name> [var=value ...]");
+        println!("---");
+        return Ok(true);
+    }
+
+    let template = Template::load(name)?;
+    let mut values = templates::parse_assignments(assignments)?;
+    let variables = template.variables();
+    for key in values.keys().filter(|key| !variables.iter().any(|v| &v.name == *key)) {
+        println!("Note: template '{}' has no variable '{}'.", template.name, key);
+    }
+    for variable in variables {
+        if values.contains_key(&variable.name) || variable.default.is_some() {
+            continue;
+        }
+        let Some(value) = read_variable(&variable.name)? else {
+            println!("Cancelled.");
+            println!("---");
+            return Ok(false);
+        };
+        values.insert(variable.name, value);
+    }
+
+    let mut template_config = config.clone();
+    if let Some(provider) = &template.provider {
+        template_config.active_provider = provider.clone();
+    }
+    if let Some(model) = &template.model {
+        let provider = template_config.active_provider.clone();
+        template_config.set_provider_model(&provider, model.clone());
+    }
+    println!("Using template '{}'.", template.name);
+    let retrieve = state.rag_always;
+    handle_llm_prompt(&template.render(&values), &template_config, client, state, retrieve, template.temperature).await
+}
+
+// Asks for a template variable until a value is given; `None` on end of input
+fn read_variable(name: &str) -> Result<Option<String>> {
+    loop {
+        print!("{}: ", name);
+        io::stdout().flush().context("Flush failed")?;
+        let mut buf = String::new();
+        if io::stdin().read_line(&mut buf).context("Read failed")? == 0 {
+            return Ok(None);
+        }
+        if !buf.trim().is_empty() {
+            return Ok(Some(buf.trim().to_string()));
+        }
+    }
+}
+
+// --- Code Blocks ---
+// `/code` lists the fenced blocks in the last answer; `save`, `copy` and
+// `run` act on one of them by number.
+fn handle_code_command(state: &SessionState, args: &[&str], args_str: &str) -> Result<()> {
+    const USAGE: &str = "Usage: /code | /code save <n> <path> | /code copy <n> | /code run <n>";
+    let Some(answer) = state
+        .conversation
+        .messages()
+        .iter()
+        .rev()
+        .find(|m| m.role == Role::Assistant && !m.content.trim().is_empty())
+    else {
+        println!("No answer yet.");
+        println!("---");
+        return Ok(());
+    };
+    let blocks = codeblocks::extract(&answer.content);
+    if blocks.is_empty() {
+        println!("The last answer has no code blocks.");
+        println!("---");
+        return Ok(());
+    }
+    let block = |n: &str| n.parse::<usize>().ok().and_then(|n| n.checked_sub(1)).and_then(|i| blocks.get(i));
+
+    match args {
+        [] => {
+            println!("Code blocks in the last answer:");
+            for (i, block) in blocks.iter().enumerate() {
+                let language = if block.language.is_empty() { "text" } else { &block.language };
+                let first = block.code.lines().find(|l| !l.trim().is_empty()).unwrap_or("").trim();
+                println!("  {}. [{}] {} line(s): {}", i + 1, language, block.line_count(), first);
+            }
+        }
+        ["save", n, _, ..] => match block(n) {
+            Some(block) => {
+                let path = attachments::expand_home(trailing_path(skip_words(args_str, 2)));
+                if path.exists() && !confirm(&format!("{} exists. Overwrite?", path.display()))? {
+                    println!("Not saved.");
+                } else {
+                    std::fs::write(&path, format!("{}\n", block.code))
+                        .context(format!("Failed to write {:?}", path))?;
+                    println!("Saved block {} to {}.", n, path.display());
+                }
+            }
+            None => println!("No code block {}; there are {}.", n, blocks.len()),
+        },
+        ["copy", n] => match block(n) {
+            Some(block) => {
+                codeblocks::copy_to_clipboard(&block.code)?;
+                println!("Copied block {} ({} lines) to the clipboard.", n, block.line_count());
+            }
+            None => println!("No code block {}; there are {}.", n, blocks.len()),
+        },
+        ["run", n] => match block(n) {
+            // The command output ends with its own separator
+            Some(block) => return run_code_block(block),
+            None => println!("No code block {}; there are {}.", n, blocks.len()),
+        },
+        _ => println!("{}", USAGE),
+    }
+    println!("---");
+    Ok(())
+}
+
+// Runs a shell or Python block from a temporary script, after showing it
+fn run_code_block(block: &CodeBlock) -> Result<()> {
+    let Some((interpreter, extension)) = block.interpreter() else {
+        println!("Only shell and Python blocks can be run (this one is '{}').", block.language);
+        println!("---");
+        return Ok(());
+    };
+    println!("{}", block.code);
+    if !confirm(&format!("Run this with {}?", interpreter))? {
+        println!("Not run.");
+        println!("---");
+        return Ok(());
+    }
+    // Removed (with its private directory) when `script` is dropped
+    let script = shell::PrivateTempFile::create(&format!("block.{}", extension), &block.code)?;
+    shell::run_shell_command(&format!("{} '{}'", interpreter, script.path().display())).map(|_| ())
+}
+
+fn handle_tools_command(config: &Config, state: &mut SessionState, args: &[&str]) {
+    match args {
+        [] => {
+            println!("Tools: {}", if state.tools_enabled { "on" } else { "off" });
+            for spec in state.tools.specs() {
+                println!("  {:<12} {}", spec.name, spec.description);
+            }
+            println!("  Max steps:     {}", config.agent_max_steps);
+            println!("  Shell sandbox: {}", config.tool_sandbox);
+            println!("  Shell timeout: {}s", config.tool_timeout_secs);
+            println!("Usage: /tools [on|off]");
+        }
+        ["on"] => {
+            state.tools_enabled = true;
+            println!("Tools on: the model may call the tools listed by /tools.");
+        }
+        ["off"] => {
+            state.tools_enabled = false;
+            println!("Tools off.");
+        }
+        _ => println!("Usage: /tools [on|off]"),
+    }
+    println!("---");
+}
+
+fn handle_mcp_command(config: &Config, state: &SessionState, args: &[&str]) {
+    match args {
+        ["list"] => {
+            if config.mcp_servers.is_empty() {
+                println!("No MCP servers configured (add them under \"mcp_servers\" in {:?}).", Config::config_file_path());
+            }
+            for (name, server_config) in &config.mcp_servers {
+                let command = std::iter::once(server_config.command.as_str())
+                    .chain(server_config.args.iter().map(String::as_str))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                match state.mcp_servers.iter().find(|s| &s.name == name) {
+                    Some(server) => {
+                        let trust = if server.trusted { ", trusted" } else { "" };
+                        println!(
+                            "{} (running: {} tools, {} resources, {} prompts{}) - {}",
+                            name, server.tools.len(), server.resources.len(), server.prompts.len(), trust, command
+                        );
+                        for resource in &server.resources {
+                            println!("  resource {} - {}", resource.uri, resource.description.as_deref().unwrap_or(&resource.name));
+                        }
+                        for prompt in &server.prompts {
+                            println!("  prompt   {} - {}", prompt.name, prompt.description.as_deref().unwrap_or(""));
+                        }
+                    }
+                    None => println!("{} (not running) - {}", name, command),
+                }
+            }
+        }
+        ["tools"] => {
+            let mut any = false;
+            for server in &state.mcp_servers {
+                for tool in &server.tools {
+                    any = true;
+                    let name = format!("{}__{}", server.name, tool.name);
+                    println!("  {:<24} {}", name, tool.description.as_deref().unwrap_or(""));
+                }
+            }
+            if !any {
+                println!("No MCP tools available.");
+            } else if !state.tools_enabled {
+                println!("Tools are off; use /tools on to let the model call them.");
+            }
+        }
+        _ => println!("Usage: /mcp list | /mcp tools"),
+    }
+    println!("---");
+}
+
+fn handle_usage_command(state: &SessionState) {
+    let totals = &state.usage;
+    println!("Session Usage:");
+    println!("  Requests:          {}", totals.requests);
+    println!("  Prompt Tokens:     {}", totals.prompt_tokens);
+    println!("  Completion Tokens: {}", totals.completion_tokens);
+    println!("  Total Tokens:      {}", totals.total_tokens());
+    println!("  Total Time:        {:.2}s", totals.total_duration.as_secs_f64());
+    if !state.usage_by_model.is_empty() {
+        println!("By Model:");
+        for (model, t) in &state.usage_by_model {
+            println!(
+                "  {} - {} requests, {} in / {} out, {:.2}s",
+                model, t.requests, t.prompt_tokens, t.completion_tokens, t.total_duration.as_secs_f64()
+            );
+        }
+    }
+    println!("---");
+}
+
+async fn handle_context_command(
+    config: &mut Config,
+    client: &Client,
+    state: &mut SessionState,
+    args: &[&str],
+) -> Result<()> {
+    match args {
+        [] => {
+            let tokenizer = Tokenizer::for_model(config.get_active_model_name());
+            let window = state.context_window(config, client).await;
+            let tokens = tokenizer.count_messages(state.conversation.messages());
+            println!("Context:");
+            if state.conversation.is_empty() {
+                println!("  Messages:  none (new conversation)");
+            } else {
+                println!("  Messages:  {}", state.conversation.len());
+            }
+            println!(
+                "  Tokens:    {}{} ({})",
+                if tokenizer.is_exact() { "" } else { "~" },
+                tokens,
+                tokenizer.name()
+            );
+            println!("  Window:    {} (reserve {} for the reply)", window, config.context_reserve_tokens);
+            if !state.pending_context.is_empty() {
+                println!("  Pending:   output of {} captured command(s)", state.pending_context.len());
+            }
+            if !state.pending_images.is_empty() {
+                println!("  Pending:   {} image(s)", state.pending_images.len());
+            }
+            println!("  Strategy:  {}", config.context_strategy);
+            if config.context_strategy == ContextStrategy::Summarize {
+                let summary_provider = config.summary_provider.as_ref().unwrap_or(&config.active_provider);
+                println!(
+                    "  Summarize: at {:.0}% of the window via {}:{}",
+                    config.summarize_fraction * 100.0,
+                    summary_provider,
+                    config.summary_model.as_deref().unwrap_or(config.get_provider_model(summary_provider))
+                );
+            }
+            println!("Usage: /context [strategy <drop_oldest|keep_first|summarize|warn_only>]");
+        }
+        ["strategy", name] => match ContextStrategy::from_name(name) {
+            Some(strategy) => {
+                config.context_strategy = strategy;
+                println!("Set context strategy to {}", strategy);
+            }
+            None => println!("Unknown strategy '{}'. Use drop_oldest, keep_first, summarize or warn_only.", name),
+        },
+        _ => println!("Usage: /context [strategy <drop_oldest|keep_first|summarize|warn_only>]"),
+    }
+    println!("---");
+    Ok(())
+}
+
+fn handle_cost_command(config: &Config, state: &mut SessionState) {
+    state.ledger.refresh();
+    let budget = |b: Option<f64>| b.map_or_else(|| "None".to_string(), |v| format!("${:.2}", v));
+    println!("Cost:");
+    println!("  Session:        ${:.6}", state.cost);
+    println!(
+        "  Month ({}): ${:.6} over {} paid requests",
+        state.ledger.month(),
+        state.ledger.month_total,
+        state.ledger.month_requests
+    );
+    println!("  Soft Budget:    {}", budget(config.monthly_soft_budget));
+    println!("  Hard Budget:    {}", budget(config.monthly_hard_budget));
+    let model = config.get_active_model_name();
+    match cost::lookup_price(config, &config.active_provider, model) {
+        Some(price) => println!(
+            "  Price ({}:{}): ${} in / ${} out per 1M tokens",
+            config.active_provider, model, price.input_per_million, price.output_per_million
+        ),
+        None => println!(
+            "  Price ({}:{}): unknown (add it to 'model_prices' in config.json)",
+            config.active_provider, model
+        ),
+    }
+    println!("---");
+}
+
 fn handle_config_command(config: &Config) {
     println!("Current Configuration:");
     println!("  Active Provider: {}", config.active_provider);
@@ -712,9 +2113,26 @@ fn handle_config_command(config: &Config) {
     println!("--- Hugging Face ---");
     println!("  API Key Set: {}", config.huggingface_api_key.is_some());
     println!("  Model:       {}", config.default_huggingface_model);
+    println!("--- Fallback ---");
+    if config.fallback_providers.is_empty() {
+        println!("  Chain: (none)");
+    } else {
+        let chain: Vec<String> = config.provider_chain().iter().map(|p| p.to_string()).collect();
+        println!("  Chain: {}", chain.join(" -> "));
+    }
+    println!("  Config File: {:?}", Config::config_file_path());
     println!("---");
 }
 
+// --- Helper function for yes/no questions (default no) ---
+fn confirm(question: &str) -> Result<bool> {
+    print!("{} [y/N]: ", question);
+    io::stdout().flush().context("Flush failed")?;
+    let mut buf = String::new();
+    io::stdin().read_line(&mut buf).context("Read failed")?;
+    Ok(matches!(buf.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
 // --- Helper function for selecting a model from a list ---
 async fn select_model(models: &[String], prompt: &str) -> Result<Option<String>> {
     if models.is_empty() {
@@ -745,7 +2163,7 @@ async fn select_model(models: &[String], prompt: &str) -> Result<Option<String>>
 }
 
 // --- Help Command ---
-fn print_help() {
+fn print_help(config: &Config) {
     println!("Available Commands:");
     println!(" General:");
     println!("  /help                    - Show this help message.");
@@ -754,9 +2172,58 @@ fn print_help() {
     println!("  /model <name>            - Set default model for the active provider.");
     println!("  /model_list              - List available models for the active provider.");
     println!("  /select_model            - Interactively select a model for the active provider.");
+    println!("  /compare <p:m> <p:m> ... [--report f.md|f.json] -- <prompt>");
+    println!("                           - Send a prompt to several provider:model targets concurrently.");
+    println!("  /usage                   - Show token usage and timing totals for this session.");
+    println!("  /cost                    - Show session and monthly spend against budgets.");
+    println!("  /context [strategy <s>]  - Show context window usage or set the trimming strategy.");
+    println!("  /compact                 - Summarize older messages to free up context.");
+    println!("  /clear                   - Start a new conversation.");
+    println!("  /session save|load <name> | /session list");
+    println!("                           - Save the conversation (with its branches) or load a saved one.");
+    println!("  /export md|html|json [path]");
+    println!("                           - Write the conversation with metadata (JSON uses the OpenAI messages format).");
+    println!("  /import <file> [n|title] - Load an OpenAI messages JSON, ChatGPT conversations.json or Ollama Modelfile as a session.");
+    println!("  /search <terms>          - Full-text search past prompts and answers from all sessions.");
+    if config.record_history {
+        println!("                           Every prompt (as typed) and answer is kept in {};", history::history_path().display());
+        println!("                           set \"record_history\": false in config.json to turn this off.");
+    } else {
+        println!("                           History recording is off (\"record_history\" in config.json).");
+    }
+    println!("  /show <id>               - Display a past exchange found with /search (or `llm-chat log`).");
+    println!("  /retry [p[:m]] [-t temp] - Answer the last prompt again (optionally with another model or temperature).");
+    println!("  /edit [text]             - Edit the last prompt and resend it from that point.");
+    println!("  /undo                    - Drop the last exchange.");
+    println!("  /branches                - Show the conversation's branches (created by /retry and /edit).");
+    println!("  /checkout <branch>       - Switch to another branch.");
     println!("  /config                  - Show current configuration settings.");
     println!("  /quit | /exit            - Exit the application.");
+    println!("  /t [name] [var=val ...]    - List prompt templates or fill one in and send it (e.g. /t review file=@src/lib.rs).");
+    println!("  /editor [text]           - Compose a prompt in $EDITOR (starting from text) and send it.");
+    println!("  ``` or a trailing \\       - Continue the prompt on the next line (until the fence is closed).");
     println!("  !<command> [args...]     - Execute a shell command.");
+    println!("  !!<command> | /run <cmd> - Execute a shell command and send its output with the next prompt.");
+    println!("  /embed [-f file] [-o out] [--format jsonl|f32] <text>");
+    println!("                           - Create embeddings with the configured embedding model.");
+    println!("  /index [dir]             - Index a directory's text files for /ask (re-embeds only changed files).");
+    println!("  /ask <question>          - Answer using the most relevant indexed excerpts, with citations.");
+    println!("  /code [save <n> <path> | copy <n> | run <n>]");
+    println!("                           - List, save, copy (OSC 52) or run the code blocks in the last answer.");
+    println!("  /schema <file>|off       - Require replies to be JSON matching a schema (validated locally).");
+    println!("  /image <path>            - Send an image with the next prompt (or use @img:<path> in a prompt).");
+    println!("  /agent [on|off]          - Let the model request shell commands (each needs approval).");
+    println!("  /tools [on|off]          - Let the model call built-in and MCP tools (read_file, list_dir, run_shell, ...).");
+    println!("                           run_shell and untrusted MCP tools need approval; run_shell commands are sandboxed.");
+    println!("  /mcp list | /mcp tools   - Show configured MCP servers or the tools they provide.");
+    println!("  @<path|glob> in a prompt - Attach file contents (e.g. @src/main.rs, @docs/*.md).");
+    if !config.aliases.is_empty() {
+        println!(" Aliases (from config.json):");
+        for (name, steps) in &config.aliases {
+            let shadowed = if helper::is_app_command(name) { "  [ignored: built-in command]" } else { "" };
+            println!("  /{:<23} - {}{}", name, steps.join(" ; "), shadowed);
+        }
+    }
     println!(" Gemini Specific:");
     println!("  /gemini_config [...]     - View/Set Gemini generation parameters.");
     println!(" Groq Specific:");
@@ -765,10 +2232,70 @@ fn print_help() {
     println!("  /huggingface_config [...]       - View/Set Hugging Face generation parameters.");
     println!("Controls:");
     println!("  Up/Down Arrows           - Navigate command history.");
-    println!("  Tab                      - Complete commands/paths.");
+    println!("  Tab                      - Complete commands and their arguments (providers, models, templates, ...), paths after '!' and '@'.");
+    println!("  Right Arrow              - Accept the grey hint from history (after '/cmd ' it shows the usage).");
     println!("  Ctrl+C                   - Interrupt.");
     println!("  Ctrl+D                   - Exit.");
     println!("---");
     println!("Note: Set API keys via GEMINI_API_KEY / GROQ_API_KEY environment variables (or .env file).");
+    println!("Note: Set a fallback chain via 'fallback_providers' in config.json or LLM_FALLBACK_PROVIDERS=groq,gemini,ollama.");
     println!("---");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_with_aliases(aliases: &[(&str, &[&str])]) -> Config {
+        let mut config = Config::default();
+        for (name, steps) in aliases {
+            config.aliases.insert(name.to_string(), steps.iter().map(|s| s.to_string()).collect());
+        }
+        config
+    }
+
+    #[test]
+    fn alias_arguments_replace_every_placeholder() {
+        let config = config_with_aliases(&[("review", &["/use groq", "Review $* carefully", "Summarize $*"])]);
+        assert_eq!(
+            expand_alias("/review @src/main.rs", &config).unwrap(),
+            ["/use groq", "Review @src/main.rs carefully", "Summarize @src/main.rs"]
+        );
+        // Without arguments the placeholder is simply removed
+        assert_eq!(expand_alias("/review", &config).unwrap(), ["/use groq", "Review  carefully", "Summarize "]);
+    }
+
+    #[test]
+    fn alias_arguments_go_to_the_last_step_without_a_placeholder() {
+        let config = config_with_aliases(&[("fast", &["/use groq", "/model llama3-8b-8192"]), ("brief", &["Answer briefly:"])]);
+        assert_eq!(expand_alias("/fast", &config).unwrap(), ["/use groq", "/model llama3-8b-8192"]);
+        assert_eq!(expand_alias("/brief   why is the sky blue?  ", &config).unwrap(), ["Answer briefly: why is the sky blue?"]);
+    }
+
+    #[test]
+    fn aliases_do_not_shadow_commands_or_match_other_input() {
+        let config = config_with_aliases(&[("help", &["/quit"]), ("fast", &["/use groq"])]);
+        assert!(expand_alias("/help", &config).is_none());
+        assert!(expand_alias("/faster", &config).is_none());
+        assert!(expand_alias("fast", &config).is_none());
+        assert!(expand_alias("!fast", &config).is_none());
+    }
+
+    #[test]
+    fn trailing_paths_may_contain_spaces_or_quotes() {
+        assert_eq!(trailing_path(skip_words("md ~/my notes/chat.md", 1)), "~/my notes/chat.md");
+        assert_eq!(trailing_path(skip_words("save  2   'build script.sh' ", 2)), "build script.sh");
+        assert_eq!(trailing_path(skip_words("html \"out.html\"", 1)), "out.html");
+        assert_eq!(trailing_path(skip_words("md", 1)), "");
+    }
+
+    #[test]
+    fn path_arguments_are_split_from_what_follows() {
+        assert_eq!(split_path_argument("\"my export.json\" 2"), ("my export.json", Some("2")));
+        assert_eq!(split_path_argument("'my export.json'"), ("my export.json", None));
+        // Unquoted paths that do not exist end at the first space
+        assert_eq!(split_path_argument("missing file.json Rust"), ("missing", Some("file.json Rust")));
+        let existing = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+        assert_eq!(split_path_argument(&format!("{} borrowing", existing)), (existing, Some("borrowing")));
+    }
+}
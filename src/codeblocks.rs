@@ -0,0 +1,100 @@
+// src/codeblocks.rs
+use crate::error::Result;
+use anyhow::Context;
+use base64::Engine as _;
+use std::io::{self, Write};
+
+// --- Code Blocks ---
+// Fenced blocks (``` or ~~~) found in an answer, in order.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock {
+    // First word of the fence's info string; empty if untagged
+    pub language: String,
+    pub code: String,
+}
+
+impl CodeBlock {
+    pub fn line_count(&self) -> usize {
+        self.code.lines().count()
+    }
+
+    // The interpreter and script extension for blocks `/code run` accepts
+    pub fn interpreter(&self) -> Option<(&'static str, &'static str)> {
+        match self.language.to_lowercase().as_str() {
+            "sh" | "shell" | "bash" | "zsh" | "console" => Some(("sh", "sh")),
+            "python" | "py" | "python3" => Some(("python3", "py")),
+            _ => None,
+        }
+    }
+}
+
+pub fn extract(text: &str) -> Vec<CodeBlock> {
+    segments(text)
+        .into_iter()
+        .filter_map(|segment| match segment {
+            Segment::Code(block) => Some(block),
+            Segment::Text(_) => None,
+        })
+        .collect()
+}
+
+// A run of prose or a fenced block, for renderers that treat them differently
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Text(String),
+    Code(CodeBlock),
+}
+
+pub fn segments(text: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut prose: Vec<&str> = Vec::new();
+    // (fence character, fence length, language, lines so far)
+    let mut open: Option<(char, usize, String, Vec<&str>)> = None;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let fence_char = trimmed.chars().next().filter(|c| *c == '`' || *c == '~');
+        let fence_len = fence_char.map_or(0, |c| trimmed.chars().take_while(|x| *x == c).count());
+        match &mut open {
+            None => {
+                if let (Some(c), true) = (fence_char, fence_len >= 3) {
+                    if !prose.is_empty() {
+                        segments.push(Segment::Text(prose.join("\n")));
+                        prose.clear();
+                    }
+                    let info = trimmed[fence_len..].trim();
+                    let language = info.split_whitespace().next().unwrap_or("").to_string();
+                    open = Some((c, fence_len, language, Vec::new()));
+                } else {
+                    prose.push(line);
+                }
+            }
+            Some((c, len, _, lines)) => {
+                let closes = fence_char == Some(*c) && fence_len >= *len && trimmed[fence_len..].trim().is_empty();
+                if closes {
+                    let (_, _, language, lines) = open.take().expect("fence is open");
+                    segments.push(Segment::Code(CodeBlock { language, code: lines.join("\n") }));
+                } else {
+                    lines.push(line);
+                }
+            }
+        }
+    }
+    // An unterminated fence (e.g. a cut-off answer) still counts
+    if let Some((_, _, language, lines)) = open {
+        segments.push(Segment::Code(CodeBlock { language, code: lines.join("\n") }));
+    } else if !prose.is_empty() {
+        segments.push(Segment::Text(prose.join("\n")));
+    }
+    segments
+}
+
+// --- Clipboard ---
+// Copies text with an OSC 52 escape sequence, which most terminals (and tmux
+// with set-clipboard on) forward to the system clipboard, even over SSH.
+pub fn copy_to_clipboard(text: &str) -> Result<()> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", encoded).context("Failed to write clipboard sequence")?;
+    stdout.flush().context("Flush failed")
+}
@@ -1,9 +1,29 @@
 // src/config.rs
+use crate::context::ContextStrategy;
+use crate::cost::ModelPrice;
 use crate::error::Result;
-use std::{env, fmt};
+use crate::mcp::McpServerConfig;
+use crate::shell::SandboxMode;
+use anyhow::Context;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::{env, fmt, fs};
 use serde::{Serialize, Deserialize};
 
+// --- Constants ---
+const CONFIG_FILE: &str = "config.json";
+
+// --- Config Directory Helper ---
+// All persistent state (history, config file, ...) lives under this directory.
+pub fn config_dir() -> PathBuf {
+    let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("llm-chat");
+    fs::create_dir_all(&path).ok();
+    path
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum LlmProvider {
     Ollama,
     Gemini,
@@ -12,6 +32,25 @@ pub enum LlmProvider {
 }
 
 impl LlmProvider {
+    pub const ALL: [LlmProvider; 4] = [
+        LlmProvider::Ollama,
+        LlmProvider::Gemini,
+        LlmProvider::Groq,
+        LlmProvider::HuggingFace,
+    ];
+
+    // Parse a provider from its config name (e.g. "groq"), case-insensitively
+    pub fn from_config_name(name: &str) -> Option<LlmProvider> {
+        Self::ALL
+            .into_iter()
+            .find(|p| p.get_provider_config_name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    // Providers that bill per request; local Ollama is always free
+    pub fn is_paid(&self) -> bool {
+        !matches!(self, LlmProvider::Ollama)
+    }
+
     pub fn get_provider_name(&self) -> &str {
         match self {
             LlmProvider::Ollama => "Ollama",
@@ -32,6 +71,7 @@ impl LlmProvider {
         }
     }
 
+    #[allow(dead_code)]
     pub fn get_provider_model_name(&self) -> &str {
         match self {
             LlmProvider::Ollama => "default_ollama_model",
@@ -42,6 +82,7 @@ impl LlmProvider {
         }
     }
 
+    #[allow(dead_code)]
     pub fn get_provider_api_key_name(&self) -> &str {
         match self {
             LlmProvider::Ollama => "",
@@ -52,6 +93,7 @@ impl LlmProvider {
         }
     }
 
+    #[allow(dead_code)]
     pub fn get_provider_base_url_name(&self) -> &str {
         match self {
             LlmProvider::Ollama => "ollama_base_url",
@@ -68,7 +110,11 @@ impl fmt::Display for LlmProvider {
     }
 }
 
+// (name, display value, f32 value, u32 value, f32 minimum)
+pub type ProviderConfigEntry<'a> = (&'a str, String, Option<f32>, Option<u32>, Option<f32>);
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     // General
     pub active_provider: LlmProvider,
@@ -92,6 +138,85 @@ pub struct Config {
     // Hugging Face Specific
     pub huggingface_api_key: Option<String>,
     pub default_huggingface_model: String,
+
+    // Fallback chain: tried in order when the active provider fails
+    // with a retryable (rate limit, 5xx) or connection error.
+    pub fallback_providers: Vec<LlmProvider>,
+
+    // Cost tracking: per "provider:model" price overrides (USD per 1M tokens)
+    // and monthly budgets in USD for paid providers.
+    pub model_prices: BTreeMap<String, ModelPrice>,
+    pub monthly_soft_budget: Option<f64>,
+    pub monthly_hard_budget: Option<f64>,
+
+    // Context window management
+    pub context_strategy: ContextStrategy,
+    // Tokens kept free for the model's reply
+    pub context_reserve_tokens: u32,
+    // Warn once the conversation uses this fraction of the window
+    pub context_warn_fraction: f32,
+    // Per "provider:model" window sizes, used instead of asking the provider
+    pub context_window_overrides: BTreeMap<String, u32>,
+    // Used when the provider does not report a window size
+    pub default_context_window: u32,
+
+    // Summarization (context_strategy = "summarize"): fraction of the window
+    // at which older turns are summarized, and an optional cheaper model for it.
+    pub summarize_fraction: f32,
+    pub summary_provider: Option<LlmProvider>,
+    pub summary_model: Option<String>,
+
+    // @file attachments: per-file size limit (larger files are truncated)
+    // and the maximum number of files one prompt may attach.
+    pub attachment_max_bytes: usize,
+    pub attachment_max_files: usize,
+    // @img:/image attachments: size limit, and extra "provider:model" prefixes
+    // known to accept images (on top of the built-in list)
+    pub image_max_bytes: usize,
+    pub vision_models: Vec<String>,
+
+    // Agent mode: commands starting with an allow-listed prefix run without
+    // confirmation, commands containing a deny-listed pattern never run.
+    pub agent_max_steps: usize,
+    pub agent_allow_commands: Vec<String>,
+    pub agent_deny_commands: Vec<String>,
+
+    // Tool calling (see /tools): the run_shell tool uses the agent allow/deny
+    // lists above, runs in a sandbox ("auto", "bwrap", "unshare" or "off")
+    // and is killed after this many seconds.
+    pub tool_sandbox: SandboxMode,
+    pub tool_timeout_secs: u64,
+
+    // MCP servers launched at startup, keyed by the name used in /mcp and in
+    // tool names
+    pub mcp_servers: BTreeMap<String, McpServerConfig>,
+
+    // User-defined slash commands, e.g. "fast": ["/use groq", "/model llama3-8b-8192"].
+    // Steps run in order; `$*` in a step is replaced by the arguments,
+    // otherwise they are appended to the last step.
+    pub aliases: BTreeMap<String, Vec<String>>,
+
+    // How long model lists (used by /model completion) are cached
+    pub model_cache_ttl_secs: u64,
+
+    // Record every prompt and answer in <config dir>/history.db (/search, `llm-chat log`)
+    pub record_history: bool,
+
+    // Embeddings (`embed`, /embed): provider (the active one if unset), the
+    // embedding model per provider and how many inputs go in one request.
+    // Groq has no embedding models by default.
+    pub embedding_provider: Option<LlmProvider>,
+    pub ollama_embedding_model: String,
+    pub gemini_embedding_model: String,
+    pub groq_embedding_model: Option<String>,
+    pub huggingface_embedding_model: String,
+    pub embedding_batch_size: usize,
+
+    // Retrieval (/index, /ask, --rag): lines per chunk, lines shared by
+    // neighbouring chunks, and how many chunks are added to a question
+    pub rag_chunk_lines: usize,
+    pub rag_chunk_overlap: usize,
+    pub rag_top_k: usize,
 }
 
 impl Default for Config {
@@ -114,34 +239,144 @@ impl Default for Config {
             // Hugging Face
             huggingface_api_key: None,
             default_huggingface_model: "meta-llama/Llama-2-7b-chat-hf".to_string(),
+            // Fallback
+            fallback_providers: Vec::new(),
+            // Cost
+            model_prices: BTreeMap::new(),
+            monthly_soft_budget: None,
+            monthly_hard_budget: None,
+            // Context
+            context_strategy: ContextStrategy::default(),
+            context_reserve_tokens: 1024,
+            context_warn_fraction: 0.8,
+            context_window_overrides: BTreeMap::new(),
+            default_context_window: 8192,
+            // Summarization
+            summarize_fraction: 0.75,
+            summary_provider: None,
+            summary_model: None,
+            // Attachments
+            attachment_max_bytes: 100_000,
+            attachment_max_files: 20,
+            image_max_bytes: 20_000_000,
+            vision_models: Vec::new(),
+            // Agent
+            agent_max_steps: 10,
+            agent_allow_commands: Vec::new(),
+            agent_deny_commands: ["sudo ", "rm -rf /", "mkfs", "dd if=", "shutdown", "reboot"]
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+            // Tools
+            tool_sandbox: SandboxMode::Auto,
+            tool_timeout_secs: 30,
+            // MCP
+            mcp_servers: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+            model_cache_ttl_secs: 600,
+            record_history: true,
+            // Embeddings
+            embedding_provider: None,
+            ollama_embedding_model: "nomic-embed-text".to_string(),
+            gemini_embedding_model: "text-embedding-004".to_string(),
+            groq_embedding_model: None,
+            huggingface_embedding_model: "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+            embedding_batch_size: 32,
+            // Retrieval
+            rag_chunk_lines: 40,
+            rag_chunk_overlap: 8,
+            rag_top_k: 5,
         }
     }
 }
 
 impl Config {
+    pub fn config_file_path() -> PathBuf {
+        config_dir().join(CONFIG_FILE)
+    }
+
     pub fn load() -> Result<Self> {
         dotenvy::dotenv().ok();
 
-        let mut config = Config::default();
+        // Start from the config file if present, otherwise from defaults
+        let path = Self::config_file_path();
+        let mut config = if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .context(format!("Failed to read config file {:?}", path))?;
+            serde_json::from_str::<Config>(&contents)
+                .context(format!("Failed to parse config file {:?}", path))?
+        } else {
+            Config::default()
+        };
 
-        config.gemini_api_key = env::var("GEMINI_API_KEY").ok();
+        // Environment variables take precedence over the config file
+        if let Ok(key) = env::var("GEMINI_API_KEY") {
+            config.gemini_api_key = Some(key);
+        }
         if config.active_provider == LlmProvider::Gemini && config.gemini_api_key.is_none() {
             eprintln!("Warning: GEMINI_API_KEY environment variable not set.");
         }
 
-        config.groq_api_key = env::var("GROQ_API_KEY").ok();
+        if let Ok(key) = env::var("GROQ_API_KEY") {
+            config.groq_api_key = Some(key);
+        }
         if config.active_provider == LlmProvider::Groq && config.groq_api_key.is_none() {
             eprintln!("Warning: GROQ_API_KEY environment variable not set.");
         }
 
-        config.huggingface_api_key = env::var("HUGGINGFACE_API_KEY").ok();
+        if let Ok(key) = env::var("HUGGINGFACE_API_KEY") {
+            config.huggingface_api_key = Some(key);
+        }
         if config.active_provider == LlmProvider::HuggingFace && config.huggingface_api_key.is_none() {
             eprintln!("Warning: HUGGINGFACE_API_KEY environment variable not set.");
          }
 
+        // e.g. LLM_FALLBACK_PROVIDERS=groq,gemini,ollama
+        if let Ok(chain) = env::var("LLM_FALLBACK_PROVIDERS") {
+            config.fallback_providers = Self::parse_provider_list(&chain)?;
+        }
+
+        config.normalize_aliases().context(format!("Invalid aliases in {:?}", path))?;
+
         Ok(config)
     }
 
+    // Alias names may be written with or without the leading '/'; an alias
+    // without steps would silently do nothing
+    fn normalize_aliases(&mut self) -> Result<()> {
+        self.aliases = std::mem::take(&mut self.aliases)
+            .into_iter()
+            .map(|(name, steps)| (name.trim_start_matches('/').to_string(), steps))
+            .collect();
+        match self.aliases.iter().find(|(_, steps)| steps.is_empty()) {
+            Some((name, _)) => Err(anyhow::anyhow!("Alias '/{}' has no steps", name)),
+            None => Ok(()),
+        }
+    }
+
+    // Parse a comma or whitespace separated list of provider names
+    pub fn parse_provider_list(list: &str) -> Result<Vec<LlmProvider>> {
+        list.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                LlmProvider::from_config_name(name)
+                    .ok_or_else(|| anyhow::anyhow!("Unknown provider '{}' in fallback list", name))
+            })
+            .collect()
+    }
+
+    // The active provider followed by the configured fallbacks, without duplicates
+    pub fn provider_chain(&self) -> Vec<LlmProvider> {
+        let mut chain = vec![self.active_provider.clone()];
+        for provider in &self.fallback_providers {
+            if !chain.contains(provider) {
+                chain.push(provider.clone());
+            }
+        }
+        chain
+    }
+
+    #[allow(dead_code)]
     pub fn reset_to_default(&mut self) {
         *self = Config::default();
     }
@@ -159,10 +394,40 @@ impl Config {
     }
     // --- END FIX ---
 
+    // Helper to get the model configured for any provider
+    pub fn get_provider_model(&self, provider: &LlmProvider) -> &str {
+        match provider {
+            LlmProvider::Ollama => &self.default_ollama_model,
+            LlmProvider::Gemini => &self.default_gemini_model,
+            LlmProvider::Groq => &self.default_groq_model,
+            LlmProvider::HuggingFace => &self.default_huggingface_model,
+        }
+    }
+
+    // Provider and model used for embeddings
+    pub fn embedding_provider(&self) -> &LlmProvider {
+        self.embedding_provider.as_ref().unwrap_or(&self.active_provider)
+    }
+
+    pub fn get_embedding_model(&self, provider: &LlmProvider) -> Option<&str> {
+        match provider {
+            LlmProvider::Ollama => Some(&self.ollama_embedding_model),
+            LlmProvider::Gemini => Some(&self.gemini_embedding_model),
+            LlmProvider::Groq => self.groq_embedding_model.as_deref(),
+            LlmProvider::HuggingFace => Some(&self.huggingface_embedding_model),
+        }
+    }
+
     // Helper to get the API key for the active provider (if applicable)
     // This function was actually correct before.
+    #[allow(dead_code)]
     pub fn get_active_api_key(&self) -> Option<&str> {
-        match self.active_provider {
+        self.get_provider_api_key(&self.active_provider)
+    }
+
+    // Helper to get the API key for any provider (if applicable)
+    pub fn get_provider_api_key(&self, provider: &LlmProvider) -> Option<&str> {
+        match provider {
             LlmProvider::Ollama => None,
             LlmProvider::Gemini => self.gemini_api_key.as_deref(),
             LlmProvider::Groq => self.groq_api_key.as_deref(),
@@ -171,7 +436,7 @@ impl Config {
         }
     }
 
-    pub fn get_provider_config(&self, provider: &LlmProvider) -> Vec<(&str, String, Option<f32>, Option<u32>, Option<f32>)> {
+    pub fn get_provider_config(&self, provider: &LlmProvider) -> Vec<ProviderConfigEntry<'_>> {
         match provider {
             LlmProvider::Ollama => vec![
                 ("ollama_base_url", self.ollama_base_url.clone(), None, None, None),
@@ -204,3 +469,20 @@ impl Config {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aliases_lose_the_slash_and_need_steps() {
+        let mut config = Config::default();
+        config.aliases.insert("/fast".to_string(), vec!["/use groq".to_string()]);
+        config.normalize_aliases().unwrap();
+        assert_eq!(config.aliases.keys().collect::<Vec<_>>(), ["fast"]);
+
+        config.aliases.insert("noop".to_string(), Vec::new());
+        let error = config.normalize_aliases().unwrap_err();
+        assert_eq!(error.to_string(), "Alias '/noop' has no steps");
+    }
+}
@@ -0,0 +1,212 @@
+// src/context.rs
+use crate::llm::message::{Message, Role};
+use crate::tokenizer::Tokenizer;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// --- Context Strategy ---
+// What to do when the conversation no longer fits the model's context window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextStrategy {
+    // Drop the oldest turns first
+    #[default]
+    DropOldest,
+    // Keep the first exchange (it usually sets up the task), drop the turns after it
+    KeepFirst,
+    // Replace older turns with a model-written summary
+    Summarize,
+    // Only warn; send the conversation unchanged
+    WarnOnly,
+}
+
+impl ContextStrategy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "drop_oldest" => Some(ContextStrategy::DropOldest),
+            "keep_first" => Some(ContextStrategy::KeepFirst),
+            "summarize" => Some(ContextStrategy::Summarize),
+            "warn_only" => Some(ContextStrategy::WarnOnly),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ContextStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ContextStrategy::DropOldest => "drop_oldest",
+            ContextStrategy::KeepFirst => "keep_first",
+            ContextStrategy::Summarize => "summarize",
+            ContextStrategy::WarnOnly => "warn_only",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// --- Trimming ---
+
+// Index of the first message that may be dropped under the given strategy
+fn first_droppable(messages: &[Message], strategy: ContextStrategy) -> usize {
+    match strategy {
+        ContextStrategy::KeepFirst => messages
+            .iter()
+            .position(|m| m.role == Role::Assistant && m.tool_calls.is_empty())
+            .map_or(0, |i| i + 1),
+        _ => 0,
+    }
+}
+
+// Drops old messages until the conversation fits in `limit` tokens.
+// System messages and the latest message are never dropped, a user message is
+// dropped together with the replies that follow it, and tool results are
+// never left without the call that requested them.
+// Returns the number of messages removed.
+pub fn trim_to_fit(
+    messages: &mut Vec<Message>,
+    tokenizer: &Tokenizer,
+    limit: usize,
+    strategy: ContextStrategy,
+) -> usize {
+    if strategy == ContextStrategy::WarnOnly {
+        return 0;
+    }
+    let start = first_droppable(messages, strategy);
+    let mut dropped = 0;
+
+    while tokenizer.count_messages(messages) > limit {
+        let last = messages.len().saturating_sub(1);
+        let Some(index) = (start..last).find(|&i| messages[i].role != Role::System) else {
+            break;
+        };
+        let removed = messages.remove(index);
+        dropped += 1;
+        while index < messages.len().saturating_sub(1)
+            && match messages[index].role {
+                Role::Tool => true,
+                Role::Assistant => removed.role == Role::User,
+                _ => false,
+            }
+        {
+            messages.remove(index);
+            dropped += 1;
+        }
+    }
+    dropped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::message::ToolCall;
+
+    // Messages are labeled "u1", "a1", ...; "c" marks a tool call, "t" its result
+    fn message(label: &str) -> Message {
+        match &label[..1] {
+            "s" => Message::system(label),
+            "u" => Message::user(label),
+            "a" => Message::assistant(label),
+            "c" => Message::assistant_with_tool_calls(label, vec![call(label)]),
+            "t" => Message::tool_result(&call(label), label),
+            _ => unreachable!("unknown label {}", label),
+        }
+    }
+
+    fn call(label: &str) -> ToolCall {
+        ToolCall { id: label[1..].to_string(), name: "list_dir".to_string(), arguments: serde_json::json!({}) }
+    }
+
+    fn messages(labels: &[&str]) -> Vec<Message> {
+        labels.iter().map(|label| message(label)).collect()
+    }
+
+    struct Case {
+        name: &'static str,
+        strategy: ContextStrategy,
+        messages: &'static [&'static str],
+        // Token limit; None means exactly what `expected` needs
+        limit: Option<usize>,
+        expected: &'static [&'static str],
+    }
+
+    #[test]
+    fn trim_to_fit_follows_the_strategy() {
+        let conversation = &["s", "u1", "a1", "u2", "a2", "u3"];
+        let with_tools = &["s", "u1", "c1", "t1", "c2", "t2", "a1", "u2", "a2", "u3"];
+        let cases = [
+            Case {
+                name: "drop_oldest drops the first exchange",
+                strategy: ContextStrategy::DropOldest,
+                messages: conversation,
+                limit: None,
+                expected: &["s", "u2", "a2", "u3"],
+            },
+            Case {
+                name: "summarize falls back to dropping the oldest",
+                strategy: ContextStrategy::Summarize,
+                messages: conversation,
+                limit: None,
+                expected: &["s", "u2", "a2", "u3"],
+            },
+            Case {
+                name: "keep_first drops the exchange after the first",
+                strategy: ContextStrategy::KeepFirst,
+                messages: conversation,
+                limit: None,
+                expected: &["s", "u1", "a1", "u3"],
+            },
+            Case {
+                name: "system prompts and the latest message always stay",
+                strategy: ContextStrategy::DropOldest,
+                messages: conversation,
+                limit: Some(0),
+                expected: &["s", "u3"],
+            },
+            Case {
+                name: "keep_first never drops the first exchange",
+                strategy: ContextStrategy::KeepFirst,
+                messages: conversation,
+                limit: Some(0),
+                expected: &["s", "u1", "a1", "u3"],
+            },
+            Case {
+                name: "warn_only sends everything",
+                strategy: ContextStrategy::WarnOnly,
+                messages: conversation,
+                limit: Some(0),
+                expected: conversation,
+            },
+            Case {
+                name: "a prompt goes with its tool calls and results",
+                strategy: ContextStrategy::DropOldest,
+                messages: with_tools,
+                limit: None,
+                expected: &["s", "u2", "a2", "u3"],
+            },
+            Case {
+                name: "keep_first keeps the tool calls of the first exchange",
+                strategy: ContextStrategy::KeepFirst,
+                messages: with_tools,
+                limit: None,
+                expected: &["s", "u1", "c1", "t1", "c2", "t2", "a1", "u3"],
+            },
+            Case {
+                name: "a leading tool call goes with its results",
+                strategy: ContextStrategy::DropOldest,
+                messages: &["c1", "t1", "a1", "u2"],
+                limit: None,
+                expected: &["a1", "u2"],
+            },
+        ];
+
+        let tokenizer = Tokenizer::Heuristic;
+        for case in cases {
+            let mut trimmed = messages(case.messages);
+            let limit = case.limit.unwrap_or_else(|| tokenizer.count_messages(&messages(case.expected)));
+            let dropped = trim_to_fit(&mut trimmed, &tokenizer, limit, case.strategy);
+            let labels: Vec<&str> = trimmed.iter().map(|m| m.content.as_str()).collect();
+            assert_eq!(labels, case.expected, "{}", case.name);
+            assert_eq!(dropped, case.messages.len() - case.expected.len(), "{}", case.name);
+        }
+    }
+}
@@ -0,0 +1,386 @@
+// src/conversation.rs
+use crate::config;
+use crate::error::Result;
+use crate::llm::message::{Message, Role};
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+// --- Constants ---
+const SESSIONS_DIR: &str = "sessions";
+
+// --- Conversation ---
+// The message history sent to the model on every turn, kept as a tree of
+// branches. Each branch holds its full message list; forking copies the
+// shared prefix and remembers where it came from. Only the active branch is
+// sent and modified.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conversation {
+    branches: Vec<Branch>,
+    active: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Branch {
+    messages: Vec<Message>,
+    // Indexes of the user prompts that started each turn
+    turns: Vec<usize>,
+    // (branch, messages shared with it) for forked branches
+    parent: Option<(usize, usize)>,
+}
+
+impl Branch {
+    pub fn turn_count(&self) -> usize {
+        self.turns.len()
+    }
+
+    pub fn parent(&self) -> Option<(usize, usize)> {
+        self.parent
+    }
+
+    // The prompt that started the latest turn
+    pub fn last_prompt(&self) -> Option<&str> {
+        self.turns.last().map(|&i| self.messages[i].content.as_str())
+    }
+}
+
+impl Default for Conversation {
+    fn default() -> Self {
+        Conversation { branches: vec![Branch::default()], active: 0 }
+    }
+}
+
+impl Conversation {
+    // A single-branch conversation from existing messages (e.g. imports);
+    // every user message starts a turn
+    pub fn from_messages(messages: Vec<Message>) -> Conversation {
+        let mut conversation = Conversation::default();
+        for message in messages {
+            if message.role == Role::User {
+                conversation.begin_turn(message);
+            } else {
+                conversation.push(message);
+            }
+        }
+        conversation
+    }
+
+    fn branch(&self) -> &Branch {
+        &self.branches[self.active]
+    }
+
+    fn branch_mut(&mut self) -> &mut Branch {
+        &mut self.branches[self.active]
+    }
+
+    pub fn messages(&self) -> &[Message] {
+        &self.branch().messages
+    }
+
+    // Rewrites the active branch in place (trimming, compaction). Messages may
+    // be removed or replaced anywhere, so turn markers are found again by the
+    // prompts they pointed at.
+    pub fn rewrite<R>(&mut self, f: impl FnOnce(&mut Vec<Message>) -> R) -> R {
+        let branch = self.branch_mut();
+        let prompts: Vec<Message> = branch.turns.iter().map(|&t| branch.messages[t].clone()).collect();
+        let result = f(&mut branch.messages);
+        // Match in order so a repeated prompt keeps its own turn
+        let mut next = 0;
+        branch.turns.clear();
+        for (i, message) in branch.messages.iter().enumerate() {
+            if let Some(offset) = prompts[next..].iter().position(|prompt| same_prompt(prompt, message)) {
+                branch.turns.push(i);
+                next += offset + 1;
+            }
+        }
+        result
+    }
+
+    pub fn push(&mut self, message: Message) {
+        self.branch_mut().messages.push(message);
+    }
+
+    // Pushes the user prompt that starts a new turn
+    pub fn begin_turn(&mut self, message: Message) {
+        let branch = self.branch_mut();
+        branch.turns.push(branch.messages.len());
+        branch.messages.push(message);
+    }
+
+    // Index of the prompt that started the latest turn
+    pub fn last_turn(&self) -> Option<usize> {
+        self.branch().turns.last().copied()
+    }
+
+    // Drops everything after the first `len` messages
+    pub fn truncate(&mut self, len: usize) {
+        let branch = self.branch_mut();
+        branch.messages.truncate(len);
+        branch.turns.retain(|&t| t < len);
+    }
+
+    // Starts a new conversation, forgetting all branches
+    pub fn clear(&mut self) {
+        *self = Conversation::default();
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages().is_empty()
+    }
+
+    // --- Branches ---
+
+    pub fn branches(&self) -> &[Branch] {
+        &self.branches
+    }
+
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    // Starts a new branch sharing the first `len` messages of the active one
+    // and switches to it. Returns its number.
+    pub fn fork(&mut self, len: usize) -> usize {
+        let current = self.branch();
+        let len = len.min(current.messages.len());
+        // Hang the fork off the oldest branch that shares the whole prefix
+        let mut parent = self.active;
+        while let Some((grandparent, shared)) = self.branches[parent].parent {
+            if len > shared {
+                break;
+            }
+            parent = grandparent;
+        }
+        let branch = Branch {
+            messages: current.messages[..len].to_vec(),
+            turns: current.turns.iter().copied().filter(|&t| t < len).collect(),
+            parent: Some((parent, len)),
+        };
+        self.branches.push(branch);
+        self.active = self.branches.len() - 1;
+        self.active
+    }
+
+    // Removes the active branch if it is the newest one (a fork whose first
+    // request failed) and returns to its parent
+    pub fn abandon_fork(&mut self) {
+        if self.active + 1 != self.branches.len() {
+            return;
+        }
+        if let Some((parent, _)) = self.branch().parent {
+            self.branches.pop();
+            self.active = parent;
+        }
+    }
+
+    pub fn checkout(&mut self, branch: usize) -> bool {
+        if branch < self.branches.len() {
+            self.active = branch;
+            true
+        } else {
+            false
+        }
+    }
+
+    // --- Saved Sessions ---
+    // Whole conversations (all branches) as JSON under <config dir>/sessions/.
+
+    pub fn save_session(&self, name: &str) -> Result<PathBuf> {
+        let path = session_path(name)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context(format!("Failed to create {:?}", parent))?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?).context(format!("Failed to write {:?}", path))?;
+        Ok(path)
+    }
+
+    pub fn load_session(name: &str) -> Result<Conversation> {
+        let path = session_path(name)?;
+        let text = fs::read_to_string(&path).map_err(|_| anyhow!("No saved session '{}'", name))?;
+        let conversation: Conversation =
+            serde_json::from_str(&text).context(format!("Failed to parse session {:?}", path))?;
+        conversation.validate().map_err(|e| anyhow!("Session {:?} is corrupt: {}", path, e))?;
+        Ok(conversation)
+    }
+
+    // Checks the indexes a hand-edited or truncated session file could break.
+    // Forks are appended, so a parent is always an older branch; that also
+    // rules out cycles.
+    fn validate(&self) -> Result<()> {
+        if self.active >= self.branches.len() {
+            return Err(anyhow!("no branch {}", self.active));
+        }
+        for (id, branch) in self.branches.iter().enumerate() {
+            let is_prompt = |t: usize| branch.messages.get(t).is_some_and(|m| m.role == Role::User);
+            if let Some(&turn) = branch.turns.iter().find(|&&t| !is_prompt(t)) {
+                return Err(anyhow!("branch {} has no user prompt at message {}", id, turn));
+            }
+            if let Some((parent, _)) = branch.parent.filter(|&(parent, _)| parent >= id) {
+                return Err(anyhow!("branch {} has an invalid parent {}", id, parent));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn same_prompt(a: &Message, b: &Message) -> bool {
+    a.role == b.role && a.content == b.content && a.timestamp == b.timestamp
+}
+
+fn session_path(name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(anyhow!("Invalid session name '{}'", name));
+    }
+    Ok(config::config_dir().join(SESSIONS_DIR).join(format!("{}.json", name)))
+}
+
+// A valid session name derived from free text (titles, file names) that does
+// not collide with an existing session
+pub fn unique_session_name(base: &str) -> String {
+    let mut slug = String::new();
+    for c in base.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug: String = slug.trim_end_matches('-').chars().take(40).collect();
+    let slug = if slug.is_empty() { "imported".to_string() } else { slug.trim_end_matches('-').to_string() };
+    let existing = session_names();
+    (1..)
+        .map(|n| if n == 1 { slug.clone() } else { format!("{}-{}", slug, n) })
+        .find(|name| !existing.contains(name))
+        .expect("unbounded range")
+}
+
+// Saved session names, sorted; used by /session list and tab completion
+pub fn session_names() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(config::config_dir().join(SESSIONS_DIR))
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|e| e == "json"))
+                .filter_map(|path| path.file_stem().map(|s| s.to_string_lossy().into_owned()))
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A conversation with one exchange per prompt
+    fn conversation(prompts: &[&str]) -> Conversation {
+        let mut conversation = Conversation::default();
+        for prompt in prompts {
+            conversation.begin_turn(Message::user(*prompt));
+            conversation.push(Message::assistant(format!("re: {}", prompt)));
+        }
+        conversation
+    }
+
+    fn prompts(conversation: &Conversation) -> Vec<&str> {
+        conversation.branch().turns.iter().map(|&t| conversation.messages()[t].content.as_str()).collect()
+    }
+
+    #[test]
+    fn fork_copies_the_prefix_and_hangs_off_the_oldest_sharing_branch() {
+        let mut conversation = conversation(&["a", "b", "c"]);
+        // Retry of the last turn: shares a, b and the prompt c
+        let retry = conversation.fork(5);
+        assert_eq!(retry, 1);
+        assert_eq!(conversation.active(), 1);
+        assert_eq!(conversation.len(), 5);
+        assert_eq!(prompts(&conversation), ["a", "b", "c"]);
+        assert_eq!(conversation.branches()[1].parent(), Some((0, 5)));
+
+        // Forking the retry before its fork point shares branch 0 as well
+        let edit = conversation.fork(2);
+        assert_eq!(conversation.branches()[edit].parent(), Some((0, 2)));
+        assert_eq!(prompts(&conversation), ["a"]);
+
+        // Past the fork point it stays on the retry branch
+        conversation.checkout(retry);
+        conversation.push(Message::assistant("re: c, again"));
+        let deeper = conversation.fork(6);
+        assert_eq!(conversation.branches()[deeper].parent(), Some((retry, 6)));
+        assert_eq!(conversation.fork(99), 4);
+        assert_eq!(conversation.len(), 6);
+    }
+
+    #[test]
+    fn abandon_fork_only_drops_the_newest_branch() {
+        let mut conversation = conversation(&["a", "b"]);
+        conversation.fork(2);
+        conversation.abandon_fork();
+        assert_eq!(conversation.branches().len(), 1);
+        assert_eq!(conversation.active(), 0);
+
+        // The root has no parent to return to
+        conversation.abandon_fork();
+        assert_eq!(conversation.branches().len(), 1);
+
+        // An older branch is kept
+        conversation.fork(2);
+        conversation.fork(2);
+        conversation.checkout(1);
+        conversation.abandon_fork();
+        assert_eq!(conversation.branches().len(), 3);
+        assert_eq!(conversation.active(), 1);
+    }
+
+    #[test]
+    fn rewrite_keeps_turns_when_messages_go_from_the_middle() {
+        let mut conversation = conversation(&["a", "b", "c"]);
+        // What keep_first trimming does: the first exchange stays
+        conversation.rewrite(|messages| messages.drain(2..4).count());
+        assert_eq!(prompts(&conversation), ["a", "c"]);
+        assert_eq!(conversation.branch().last_prompt(), Some("c"));
+        assert_eq!(conversation.last_turn(), Some(2));
+    }
+
+    #[test]
+    fn rewrite_keeps_turns_when_older_messages_are_replaced() {
+        let mut conversation = conversation(&["a", "b", "a"]);
+        // What compaction does: a summary replaces the first two exchanges
+        conversation.rewrite(|messages| {
+            let recent = messages.split_off(4);
+            *messages = vec![Message::system("Summary")];
+            messages.extend(recent);
+        });
+        assert_eq!(conversation.branch().turns, [1]);
+        assert_eq!(prompts(&conversation), ["a"]);
+    }
+
+    #[test]
+    fn validate_rejects_broken_indexes() {
+        assert!(conversation(&["a", "b"]).validate().is_ok());
+
+        let mut bad_active = conversation(&["a"]);
+        bad_active.active = 1;
+        assert!(bad_active.validate().is_err());
+
+        let mut not_a_prompt = conversation(&["a"]);
+        not_a_prompt.branches[0].turns.push(1);
+        assert!(not_a_prompt.validate().is_err());
+
+        let mut out_of_range = conversation(&["a"]);
+        out_of_range.branches[0].turns.push(7);
+        assert!(out_of_range.validate().is_err());
+
+        let mut cycle = conversation(&["a"]);
+        cycle.fork(1);
+        cycle.branches[0].parent = Some((1, 1));
+        assert!(cycle.validate().is_err());
+    }
+}
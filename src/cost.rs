@@ -0,0 +1,199 @@
+// src/cost.rs
+use crate::config::{self, Config, LlmProvider};
+use crate::error::Result;
+use crate::llm::usage::Usage;
+use anyhow::Context;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use tracing::{debug, warn};
+
+// --- Constants ---
+const LEDGER_DIR: &str = "ledger";
+
+// --- Price Table ---
+// Prices are in USD per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+// Built-in defaults, keyed "provider:model". The model part is matched as a
+// prefix so e.g. "gemini:gemini-1.5-pro" also covers "gemini-1.5-pro-latest".
+// Override or extend these via `model_prices` in config.json.
+const DEFAULT_PRICES: &[(&str, f64, f64)] = &[
+    ("gemini:gemini-1.5-pro", 1.25, 5.00),
+    ("gemini:gemini-1.5-flash-8b", 0.0375, 0.15),
+    ("gemini:gemini-1.5-flash", 0.075, 0.30),
+    ("gemini:gemini-1.0-pro", 0.50, 1.50),
+    ("groq:llama3-8b-8192", 0.05, 0.08),
+    ("groq:llama3-70b-8192", 0.59, 0.79),
+    ("groq:mixtral-8x7b-32768", 0.24, 0.24),
+    ("groq:gemma-7b-it", 0.07, 0.07),
+    ("groq:gemma2-9b-it", 0.20, 0.20),
+];
+
+// Looks up the price for a provider/model, preferring config overrides and
+// the longest matching key.
+pub fn lookup_price(config: &Config, provider: &LlmProvider, model: &str) -> Option<ModelPrice> {
+    if !provider.is_paid() {
+        return Some(ModelPrice { input_per_million: 0.0, output_per_million: 0.0 });
+    }
+    let key = format!("{}:{}", provider.get_provider_config_name(), model);
+
+    let overrides = config.model_prices.iter().map(|(k, p)| (k.as_str(), *p));
+    let defaults = DEFAULT_PRICES.iter().map(|(k, input, output)| {
+        (*k, ModelPrice { input_per_million: *input, output_per_million: *output })
+    });
+
+    // Config overrides win over defaults of the same or shorter length
+    overrides
+        .chain(defaults)
+        .filter(|(k, _)| key.starts_with(k))
+        .fold(None, |best: Option<(&str, ModelPrice)>, (k, p)| match best {
+            Some((best_key, _)) if best_key.len() >= k.len() => best,
+            _ => Some((k, p)),
+        })
+        .map(|(_, p)| p)
+}
+
+// Cost in USD of a single request, or `None` if the model has no known price
+// or the provider did not report token counts.
+pub fn request_cost(config: &Config, provider: &LlmProvider, model: &str, usage: &Usage) -> Option<f64> {
+    let price = lookup_price(config, provider, model)?;
+    if provider.is_paid() && usage.prompt_tokens.is_none() && usage.completion_tokens.is_none() {
+        return None;
+    }
+    let input = f64::from(usage.prompt_tokens.unwrap_or(0)) * price.input_per_million;
+    let output = f64::from(usage.completion_tokens.unwrap_or(0)) * price.output_per_million;
+    Some((input + output) / 1_000_000.0)
+}
+
+// --- Monthly Ledger ---
+// One JSON Lines file per month under <config dir>/ledger/YYYY-MM.jsonl.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub timestamp: DateTime<Local>,
+    pub provider: LlmProvider,
+    pub model: String,
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    pub cost: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct Ledger {
+    month: String,
+    pub month_total: f64,
+    pub month_requests: u32,
+}
+
+fn month_key(timestamp: &DateTime<Local>) -> String {
+    timestamp.format("%Y-%m").to_string()
+}
+
+fn ledger_path(month: &str) -> PathBuf {
+    let dir = config::config_dir().join(LEDGER_DIR);
+    fs::create_dir_all(&dir).ok();
+    dir.join(format!("{}.jsonl", month))
+}
+
+impl Ledger {
+    // Loads the running total for the current month
+    pub fn load() -> Self {
+        let month = month_key(&Local::now());
+        let mut ledger = Ledger { month, ..Ledger::default() };
+        let path = ledger_path(&ledger.month);
+        let Ok(file) = fs::File::open(&path) else {
+            return ledger;
+        };
+        for line in BufReader::new(file).lines().map_while(|l| l.ok()) {
+            match serde_json::from_str::<LedgerEntry>(&line) {
+                Ok(entry) => {
+                    ledger.month_total += entry.cost;
+                    ledger.month_requests += 1;
+                }
+                Err(e) => warn!("Skipping malformed ledger line in {:?}: {}", path, e),
+            }
+        }
+        debug!("Loaded ledger for {}: ${:.4}", ledger.month, ledger.month_total);
+        ledger
+    }
+
+    // Switches to the current month's total if the month rolled over since
+    // the ledger was loaded
+    pub fn refresh(&mut self) {
+        if month_key(&Local::now()) != self.month {
+            *self = Ledger::load();
+        }
+    }
+
+    pub fn month(&self) -> &str {
+        &self.month
+    }
+
+    pub fn record(&mut self, entry: &LedgerEntry) -> Result<()> {
+        // Start a fresh total when the month rolls over mid-session
+        let month = month_key(&entry.timestamp);
+        if month != self.month {
+            *self = Ledger { month, ..Ledger::default() };
+        }
+        let path = ledger_path(&self.month);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .context(format!("Failed to open ledger file {:?}", path))?;
+        writeln!(file, "{}", serde_json::to_string(entry)?).context("Failed to write ledger entry")?;
+        self.month_total += entry.cost;
+        self.month_requests += 1;
+        Ok(())
+    }
+}
+
+// --- Budgets ---
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BudgetStatus {
+    Ok,
+    SoftExceeded,
+    HardExceeded,
+}
+
+pub fn budget_status(config: &Config, ledger: &mut Ledger) -> BudgetStatus {
+    ledger.refresh();
+    let spent = ledger.month_total;
+    if config.monthly_hard_budget.is_some_and(|limit| spent >= limit) {
+        BudgetStatus::HardExceeded
+    } else if config.monthly_soft_budget.is_some_and(|limit| spent >= limit) {
+        BudgetStatus::SoftExceeded
+    } else {
+        BudgetStatus::Ok
+    }
+}
+
+// Paid providers that must not be used under the current budget
+pub fn blocked_providers(config: &Config, ledger: &mut Ledger) -> Vec<LlmProvider> {
+    if budget_status(config, ledger) == BudgetStatus::HardExceeded {
+        LlmProvider::ALL.into_iter().filter(|p| p.is_paid()).collect()
+    } else {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn budget_status_rolls_over_a_stale_month() {
+        let config = Config { monthly_hard_budget: Some(10.0), ..Config::default() };
+        let mut ledger = Ledger { month: "2000-01".to_string(), month_total: 100.0, month_requests: 3 };
+        budget_status(&config, &mut ledger);
+        assert_eq!(ledger.month(), month_key(&Local::now()));
+    }
+}
@@ -0,0 +1,131 @@
+// src/embed.rs
+use crate::config::{Config, LlmProvider};
+use crate::error::Result;
+use crate::llm;
+use anyhow::{anyhow, Context};
+use clap::{Parser, ValueEnum};
+use reqwest::Client;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufWriter, IsTerminal, Write};
+use std::path::PathBuf;
+use tracing::debug;
+
+// --- Arguments ---
+// Shared by `llm-chat embed` and the /embed command.
+#[derive(Parser, Debug, Clone)]
+#[command(name = "embed", about = "Create embeddings for text lines or files")]
+pub struct EmbedArgs {
+    /// Texts to embed, one input each; stdin lines are used when no text or file is given
+    pub texts: Vec<String>,
+
+    /// Embed a whole file as one input (repeatable)
+    #[arg(short, long = "file", value_name = "FILE")]
+    pub files: Vec<PathBuf>,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = EmbedFormat::Jsonl)]
+    pub format: EmbedFormat,
+
+    /// Write to this file instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Provider to use instead of the configured embedding provider
+    #[arg(long)]
+    pub provider: Option<String>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbedFormat {
+    // One JSON object per input
+    Jsonl,
+    // Raw little-endian f32 values, vectors back to back
+    F32,
+}
+
+// --- Inputs ---
+struct EmbedInput {
+    // The file path for file inputs, the text itself otherwise
+    source: String,
+    text: String,
+}
+
+#[derive(Serialize)]
+struct EmbedRecord<'a> {
+    index: usize,
+    source: &'a str,
+    embedding: &'a [f32],
+}
+
+fn collect_inputs(args: &EmbedArgs, read_stdin: bool) -> Result<Vec<EmbedInput>> {
+    let mut inputs: Vec<EmbedInput> = args
+        .texts
+        .iter()
+        .map(|text| EmbedInput { source: text.clone(), text: text.clone() })
+        .collect();
+    for path in &args.files {
+        let text = fs::read_to_string(path).context(format!("Failed to read {:?}", path))?;
+        inputs.push(EmbedInput { source: path.display().to_string(), text });
+    }
+    if inputs.is_empty() && read_stdin {
+        for line in io::stdin().lock().lines() {
+            let line = line.context("Failed to read stdin")?;
+            if !line.trim().is_empty() {
+                inputs.push(EmbedInput { source: line.clone(), text: line });
+            }
+        }
+    }
+    Ok(inputs.into_iter().filter(|input| !input.text.trim().is_empty()).collect())
+}
+
+// --- Embedding ---
+// Embeds the inputs in batches of `embedding_batch_size` and writes the
+// vectors as they arrive. `read_stdin` is false in the REPL, where stdin is
+// the terminal. Returns (inputs embedded, dimensions).
+pub async fn run_embed(config: &Config, client: &Client, args: &EmbedArgs, read_stdin: bool) -> Result<(usize, usize)> {
+    let provider = match &args.provider {
+        Some(name) => LlmProvider::from_config_name(name).ok_or_else(|| anyhow!("Unknown provider '{}'", name))?,
+        None => config.embedding_provider().clone(),
+    };
+    let inputs = collect_inputs(args, read_stdin)?;
+    if inputs.is_empty() {
+        return Err(anyhow!("Nothing to embed: pass text, --file, or lines on stdin."));
+    }
+
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path).context(format!("Failed to create {:?}", path))?)),
+        None if args.format == EmbedFormat::F32 && io::stdout().is_terminal() => {
+            return Err(anyhow!("Refusing to write binary vectors to the terminal; use --output or a pipe."));
+        }
+        None => Box::new(BufWriter::new(io::stdout())),
+    };
+
+    let mut dims = 0;
+    let batch_size = config.embedding_batch_size.max(1);
+    for (batch_index, batch) in inputs.chunks(batch_size).enumerate() {
+        let texts: Vec<String> = batch.iter().map(|input| input.text.clone()).collect();
+        debug!("Embedding batch {} ({} inputs) via {}", batch_index, texts.len(), provider);
+        let vectors = llm::embed(client, config, &provider, &texts).await?;
+        for (offset, (input, vector)) in batch.iter().zip(&vectors).enumerate() {
+            if dims == 0 {
+                dims = vector.len();
+            } else if vector.len() != dims {
+                return Err(anyhow!("Embedding dimensions changed from {} to {}", dims, vector.len()));
+            }
+            match args.format {
+                EmbedFormat::Jsonl => {
+                    let record = EmbedRecord { index: batch_index * batch_size + offset, source: &input.source, embedding: vector };
+                    writeln!(out, "{}", serde_json::to_string(&record)?)?;
+                }
+                EmbedFormat::F32 => {
+                    for value in vector {
+                        out.write_all(&value.to_le_bytes())?;
+                    }
+                }
+            }
+        }
+    }
+    out.flush().context("Failed to write embeddings")?;
+    Ok((inputs.len(), dims))
+}
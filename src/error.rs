@@ -1,4 +1,38 @@
 // src/error.rs
+use std::fmt;
 
 // Define a standard result type for the application
 pub type Result<T> = anyhow::Result<T>;
+
+// --- HTTP Status Error ---
+// Returned by the backends when an API answers with a non-success status,
+// so callers can tell rate limits and server errors apart from other failures.
+#[derive(Debug)]
+pub struct ApiStatusError {
+    pub status: u16,
+    pub message: String,
+}
+
+impl fmt::Display for ApiStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "API request failed with status {}: {}", self.status, self.message)
+    }
+}
+
+impl std::error::Error for ApiStatusError {}
+
+// Whether a failed request is worth sending to another provider:
+// rate limits, timeouts, server errors and connection failures.
+pub fn is_retryable(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        if let Some(status_err) = cause.downcast_ref::<ApiStatusError>() {
+            return matches!(status_err.status, 408 | 429) || status_err.status >= 500;
+        }
+        if let Some(req_err) = cause.downcast_ref::<reqwest::Error>() {
+            return req_err.is_connect()
+                || req_err.is_timeout()
+                || req_err.status().is_some_and(|s| s.as_u16() == 429 || s.is_server_error());
+        }
+        false
+    })
+}
@@ -0,0 +1,405 @@
+// src/export.rs
+use crate::codeblocks::{self, Segment};
+use crate::config::LlmProvider;
+use crate::error::Result;
+use crate::llm::message::{Message, Role};
+use crate::llm::openai_compatible::ChatMessage;
+use crate::llm::usage::UsageTotals;
+use anyhow::Context;
+use chrono::{DateTime, Local};
+use serde_json::json;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// --- Formats ---
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+    Json,
+}
+
+impl ExportFormat {
+    pub const NAMES: [&'static str; 3] = ["md", "html", "json"];
+
+    pub fn from_name(name: &str) -> Option<ExportFormat> {
+        match name.to_lowercase().as_str() {
+            "md" | "markdown" => Some(ExportFormat::Markdown),
+            "html" | "htm" => Some(ExportFormat::Html),
+            "json" => Some(ExportFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+// --- Export Metadata ---
+// Session-level details written above the messages.
+pub struct ExportInfo<'a> {
+    pub provider: &'a LlmProvider,
+    pub model: &'a str,
+    // (name, value) generation parameters of the active provider
+    pub parameters: Vec<(String, String)>,
+    pub usage: &'a UsageTotals,
+    pub cost: f64,
+    pub branch: usize,
+    pub exported_at: DateTime<Local>,
+}
+
+// Where an export goes; a missing path or a directory gets a timestamped name
+pub fn output_path(format: ExportFormat, path: Option<&Path>, info: &ExportInfo) -> PathBuf {
+    let file_name = format!("conversation-{}.{}", info.exported_at.format("%Y%m%d-%H%M%S"), format.extension());
+    match path {
+        Some(path) if path.is_dir() => path.join(file_name),
+        Some(path) => path.to_path_buf(),
+        None => PathBuf::from(file_name),
+    }
+}
+
+// Writes the export to `path`, replacing any existing file
+pub fn write(format: ExportFormat, path: &Path, messages: &[Message], info: &ExportInfo) -> Result<()> {
+    let contents = match format {
+        ExportFormat::Markdown => to_markdown(messages, info),
+        ExportFormat::Html => to_html(messages, info),
+        ExportFormat::Json => serde_json::to_string_pretty(&to_json(messages, info))?,
+    };
+    fs::write(path, contents).context(format!("Failed to write export to {:?}", path))
+}
+
+fn role_title(role: Role) -> &'static str {
+    match role {
+        Role::System => "System",
+        Role::User => "User",
+        Role::Assistant => "Assistant",
+        Role::Tool => "Tool",
+    }
+}
+
+fn format_timestamp(timestamp: &DateTime<Local>) -> String {
+    timestamp.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn usage_summary(info: &ExportInfo) -> String {
+    format!(
+        "{} requests, {} prompt / {} completion tokens, {:.2}s, ${:.4}",
+        info.usage.requests,
+        info.usage.prompt_tokens,
+        info.usage.completion_tokens,
+        info.usage.total_duration.as_secs_f64(),
+        info.cost
+    )
+}
+
+fn parameter_summary(info: &ExportInfo) -> String {
+    if info.parameters.is_empty() {
+        return "provider defaults".to_string();
+    }
+    info.parameters.iter().map(|(name, value)| format!("{}={}", name, value)).collect::<Vec<_>>().join(", ")
+}
+
+// "User · 2024-05-01 10:00:00" / "Assistant · groq:llama3 · ... · tokens: ..."
+fn message_heading(message: &Message) -> String {
+    let mut parts = vec![role_title(message.role).to_string()];
+    if let Some(tool) = &message.tool_name {
+        parts[0] = format!("Tool ({})", tool);
+    }
+    if let Some(meta) = &message.meta {
+        parts.push(format!("{}:{}", meta.provider, meta.model));
+        if let Some(temperature) = meta.temperature {
+            parts.push(format!("temperature {}", temperature));
+        }
+    }
+    if let Some(timestamp) = &message.timestamp {
+        parts.push(format_timestamp(timestamp));
+    }
+    if let Some(meta) = &message.meta {
+        parts.push(meta.usage.to_string());
+    }
+    parts.join(" · ")
+}
+
+// --- Markdown ---
+fn to_markdown(messages: &[Message], info: &ExportInfo) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# Conversation\n");
+    let _ = writeln!(out, "- Exported: {}", format_timestamp(&info.exported_at));
+    let _ = writeln!(out, "- Provider: {}", info.provider);
+    let _ = writeln!(out, "- Model: {}", info.model);
+    let _ = writeln!(out, "- Parameters: {}", parameter_summary(info));
+    let _ = writeln!(out, "- Branch: {} ({} messages)", info.branch, messages.len());
+    let _ = writeln!(out, "- Usage: {}", usage_summary(info));
+    for message in messages {
+        let _ = writeln!(out, "\n---\n\n### {}\n", message_heading(message));
+        if message.role == Role::Tool {
+            let _ = writeln!(out, "```\n{}\n```", message.content.trim_end());
+        } else if !message.content.trim().is_empty() {
+            let _ = writeln!(out, "{}", message.content.trim());
+        }
+        if !message.images.is_empty() {
+            let _ = writeln!(out, "\n_{} image(s) attached_", message.images.len());
+        }
+        for call in &message.tool_calls {
+            let _ = writeln!(out, "\nCalled `{}`:\n\n```json\n{}\n```", call.name, call.arguments);
+        }
+    }
+    out
+}
+
+// --- JSON ---
+// OpenAI chat format (`model` + `messages`), so it can be sent or re-imported
+// as is; everything else goes under `metadata`.
+fn to_json(messages: &[Message], info: &ExportInfo) -> serde_json::Value {
+    let chat_messages: Vec<ChatMessage> = messages.iter().map(ChatMessage::from).collect();
+    let message_meta: Vec<serde_json::Value> = messages
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| m.timestamp.is_some() || m.meta.is_some())
+        .map(|(index, m)| {
+            let mut entry = json!({ "index": index });
+            if let Some(timestamp) = &m.timestamp {
+                entry["timestamp"] = json!(timestamp.to_rfc3339());
+            }
+            if let Some(meta) = &m.meta {
+                entry["provider"] = json!(meta.provider);
+                entry["model"] = json!(meta.model);
+                entry["temperature"] = json!(meta.temperature);
+                entry["prompt_tokens"] = json!(meta.usage.prompt_tokens);
+                entry["completion_tokens"] = json!(meta.usage.completion_tokens);
+                entry["duration_secs"] = json!(meta.usage.total_duration.as_secs_f64());
+            }
+            entry
+        })
+        .collect();
+    let parameters: serde_json::Map<String, serde_json::Value> =
+        info.parameters.iter().map(|(name, value)| (name.clone(), json!(value))).collect();
+    json!({
+        "model": info.model,
+        "messages": chat_messages,
+        "metadata": {
+            "exported_at": info.exported_at.to_rfc3339(),
+            "provider": info.provider,
+            "model": info.model,
+            "parameters": parameters,
+            "branch": info.branch,
+            "usage": {
+                "requests": info.usage.requests,
+                "prompt_tokens": info.usage.prompt_tokens,
+                "completion_tokens": info.usage.completion_tokens,
+                "duration_secs": info.usage.total_duration.as_secs_f64(),
+                "cost_usd": info.cost,
+            },
+            "messages": message_meta,
+        },
+    })
+}
+
+// --- HTML ---
+// One self-contained file: inline styles and server-side code highlighting.
+const HTML_STYLE: &str = "
+body { font-family: -apple-system, 'Segoe UI', Helvetica, Arial, sans-serif; max-width: 860px; margin: 2em auto; padding: 0 1em; color: #1f2328; background: #fff; line-height: 1.5; }
+h1 { font-size: 1.6em; border-bottom: 1px solid #d0d7de; padding-bottom: .3em; }
+.meta { color: #57606a; font-size: .9em; }
+.meta td { padding: 0 1em 0 0; }
+.message { border: 1px solid #d0d7de; border-radius: 8px; margin: 1.2em 0; padding: .6em 1em; }
+.message.user { background: #f6f8fa; }
+.message.system { background: #fff8c5; }
+.message.tool { background: #f6f8fa; font-size: .9em; }
+.heading { color: #57606a; font-size: .8em; margin-bottom: .4em; }
+.heading .role { font-weight: 600; color: #1f2328; }
+pre { background: #0d1117; color: #e6edf3; padding: .8em 1em; border-radius: 6px; overflow-x: auto; font-size: .85em; }
+code { font-family: ui-monospace, SFMono-Regular, Menlo, Consolas, monospace; }
+p code { background: #eff1f3; padding: .1em .3em; border-radius: 4px; }
+.lang { float: right; color: #8b949e; font-size: .8em; }
+.k { color: #ff7b72; } .s { color: #a5d6ff; } .c { color: #8b949e; font-style: italic; } .n { color: #79c0ff; }
+";
+
+fn to_html(messages: &[Message], info: &ExportInfo) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">");
+    let _ = writeln!(out, "<title>Conversation {}</title>", format_timestamp(&info.exported_at));
+    let _ = writeln!(out, "<style>{}</style>\n</head>\n<body>\n<h1>Conversation</h1>", HTML_STYLE);
+    let _ = writeln!(out, "<table class=\"meta\">");
+    let rows = [
+        ("Exported", format_timestamp(&info.exported_at)),
+        ("Provider", info.provider.to_string()),
+        ("Model", info.model.to_string()),
+        ("Parameters", parameter_summary(info)),
+        ("Branch", format!("{} ({} messages)", info.branch, messages.len())),
+        ("Usage", usage_summary(info)),
+    ];
+    for (name, value) in rows {
+        let _ = writeln!(out, "<tr><td>{}</td><td>{}</td></tr>", name, escape_html(&value));
+    }
+    let _ = writeln!(out, "</table>");
+    for message in messages {
+        let heading = message_heading(message);
+        let (role, rest) = heading.split_once(" · ").unwrap_or((heading.as_str(), ""));
+        let _ = writeln!(out, "<div class=\"message {}\">", message.role);
+        let separator = if rest.is_empty() { "" } else { " · " };
+        let _ = writeln!(
+            out,
+            "<div class=\"heading\"><span class=\"role\">{}</span>{}{}</div>",
+            escape_html(role),
+            separator,
+            escape_html(rest)
+        );
+        if message.role == Role::Tool {
+            let _ = writeln!(out, "<pre><code>{}</code></pre>", escape_html(message.content.trim_end()));
+        } else {
+            out.push_str(&render_markdown(&message.content));
+        }
+        if !message.images.is_empty() {
+            let _ = writeln!(out, "<p><em>{} image(s) attached</em></p>", message.images.len());
+        }
+        for call in &message.tool_calls {
+            let _ = writeln!(out, "<p>Called <code>{}</code>:</p>", escape_html(&call.name));
+            let _ = writeln!(out, "<pre><code>{}</code></pre>", highlight_code("json", &call.arguments.to_string()));
+        }
+        let _ = writeln!(out, "</div>");
+    }
+    let _ = writeln!(out, "</body>\n</html>");
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Enough Markdown for chat answers: fenced code, headings, paragraphs,
+// inline code and bold. Other syntax is shown as written.
+fn render_markdown(text: &str) -> String {
+    let mut out = String::new();
+    for segment in codeblocks::segments(text) {
+        match segment {
+            Segment::Code(block) => {
+                let label = if block.language.is_empty() {
+                    String::new()
+                } else {
+                    format!("<span class=\"lang\">{}</span>", escape_html(&block.language))
+                };
+                let _ = writeln!(out, "<pre>{}<code>{}</code></pre>", label, highlight_code(&block.language, &block.code));
+            }
+            Segment::Text(prose) => {
+                for paragraph in prose.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+                    let level = paragraph.chars().take_while(|c| *c == '#').count();
+                    if (1..=6).contains(&level) && paragraph[level..].starts_with(' ') && !paragraph.contains('\n') {
+                        // One level down, as <h1> is the page title; HTML stops at <h6>
+                        let tag = (level + 1).min(6);
+                        let _ = writeln!(out, "<h{0}>{1}</h{0}>", tag, render_inline(paragraph[level..].trim()));
+                    } else {
+                        let lines: Vec<String> = paragraph.lines().map(render_inline).collect();
+                        let _ = writeln!(out, "<p>{}</p>", lines.join("<br>\n"));
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+fn render_inline(line: &str) -> String {
+    let mut out = String::new();
+    // Odd-numbered pieces between backticks are code
+    for (i, piece) in line.split('`').enumerate() {
+        if i % 2 == 1 {
+            let _ = write!(out, "<code>{}</code>", escape_html(piece));
+        } else {
+            for (j, part) in escape_html(piece).split("**").enumerate() {
+                if j % 2 == 1 {
+                    let _ = write!(out, "<strong>{}</strong>", part);
+                } else {
+                    out.push_str(part);
+                }
+            }
+        }
+    }
+    out
+}
+
+// --- Code Highlighting ---
+// A small language-agnostic tokenizer: comments, strings, numbers and common
+// keywords get a CSS class.
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "case", "catch", "class", "const", "continue", "def", "default", "do", "elif",
+    "else", "enum", "export", "extends", "false", "fi", "finally", "fn", "for", "from", "func", "function", "if",
+    "impl", "import", "in", "interface", "let", "loop", "match", "mod", "mut", "new", "nil", "None", "null", "pub",
+    "return", "self", "Self", "static", "struct", "switch", "then", "this", "throw", "trait", "true", "True", "False",
+    "try", "type", "use", "var", "where", "while", "with", "yield",
+];
+
+fn line_comment_markers(language: &str) -> &'static [&'static str] {
+    match language.to_lowercase().as_str() {
+        "python" | "py" | "sh" | "bash" | "shell" | "zsh" | "ruby" | "rb" | "yaml" | "yml" | "toml" | "r"
+        | "dockerfile" | "makefile" | "perl" => &["#"],
+        "sql" | "lua" | "haskell" | "hs" => &["--"],
+        "json" => &[],
+        _ => &["//"],
+    }
+}
+
+// Rust lifetimes ('a) are not char literals ('a')
+fn is_lifetime(language: &str, rest: &[char]) -> bool {
+    matches!(language, "rust" | "rs") && rest.get(2) != Some(&'\'') && rest.get(1) != Some(&'\\')
+}
+
+fn highlight_code(language: &str, code: &str) -> String {
+    let comment_markers = line_comment_markers(language);
+    let chars: Vec<char> = code.chars().collect();
+    let mut out = String::with_capacity(code.len() * 2);
+    let mut i = 0;
+    let span = |out: &mut String, class: &str, text: &str| {
+        let _ = write!(out, "<span class=\"{}\">{}</span>", class, escape_html(text));
+    };
+    while i < chars.len() {
+        let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+        let c = chars[i];
+        if comment_markers.iter().any(|m| rest.starts_with(m)) {
+            let end = chars[i..].iter().position(|&c| c == '\n').map_or(chars.len(), |p| i + p);
+            span(&mut out, "c", &chars[i..end].iter().collect::<String>());
+            i = end;
+        } else if c == '"' || c == '`' || (c == '\'' && !is_lifetime(language, &chars[i..])) {
+            let mut end = i + 1;
+            while end < chars.len() && chars[end] != c && chars[end] != '\n' {
+                end += if chars[end] == '\\' { 2 } else { 1 };
+            }
+            let end = (end + 1).min(chars.len());
+            span(&mut out, "s", &chars[i..end].iter().collect::<String>());
+            i = end;
+        } else if c.is_ascii_digit() {
+            let end = chars[i..].iter().position(|c| !c.is_ascii_alphanumeric() && *c != '.' && *c != '_').map_or(chars.len(), |p| i + p);
+            span(&mut out, "n", &chars[i..end].iter().collect::<String>());
+            i = end;
+        } else if c.is_alphabetic() || c == '_' {
+            let end = chars[i..].iter().position(|c| !c.is_alphanumeric() && *c != '_').map_or(chars.len(), |p| i + p);
+            let word: String = chars[i..end].iter().collect();
+            if KEYWORDS.contains(&word.as_str()) {
+                span(&mut out, "k", &word);
+            } else {
+                out.push_str(&word);
+            }
+            i = end;
+        } else {
+            out.push_str(&escape_html(&c.to_string()));
+            i += 1;
+        }
+    }
+    out
+}
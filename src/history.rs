@@ -0,0 +1,274 @@
+// src/history.rs
+use crate::config::{self, LlmProvider};
+use crate::error::Result;
+use crate::llm::Generation;
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+use clap::Parser;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::path::PathBuf;
+
+// --- Constants ---
+const HISTORY_DB: &str = "history.db";
+
+// Exchanges plus an external-content FTS5 index kept in sync by a trigger
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS exchanges (
+    id INTEGER PRIMARY KEY,
+    timestamp INTEGER NOT NULL,
+    provider TEXT NOT NULL,
+    model TEXT NOT NULL,
+    prompt TEXT NOT NULL,
+    response TEXT NOT NULL,
+    temperature REAL,
+    prompt_tokens INTEGER,
+    completion_tokens INTEGER,
+    duration_ms INTEGER NOT NULL,
+    cost REAL
+);
+CREATE INDEX IF NOT EXISTS exchanges_timestamp ON exchanges(timestamp);
+CREATE VIRTUAL TABLE IF NOT EXISTS exchanges_fts USING fts5(
+    prompt, response, content='exchanges', content_rowid='id'
+);
+CREATE TRIGGER IF NOT EXISTS exchanges_insert AFTER INSERT ON exchanges BEGIN
+    INSERT INTO exchanges_fts(rowid, prompt, response) VALUES (new.id, new.prompt, new.response);
+END;
+";
+
+const COLUMNS: &str = "id, timestamp, provider, model, prompt, response, temperature, \
+                       prompt_tokens, completion_tokens, duration_ms, cost";
+
+// --- Exchanges ---
+// One prompt and the answer to it, as stored.
+#[derive(Debug, Clone)]
+pub struct Exchange {
+    pub id: i64,
+    pub timestamp: DateTime<Local>,
+    // Config name ("groq"), as used by --provider
+    pub provider: String,
+    pub model: String,
+    pub prompt: String,
+    pub response: String,
+    pub temperature: Option<f32>,
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    pub duration_ms: u64,
+    pub cost: Option<f64>,
+}
+
+impl Exchange {
+    fn from_row(row: &Row) -> rusqlite::Result<Exchange> {
+        let timestamp: i64 = row.get(1)?;
+        Ok(Exchange {
+            id: row.get(0)?,
+            timestamp: Local.timestamp_opt(timestamp, 0).single().unwrap_or_default(),
+            provider: row.get(2)?,
+            model: row.get(3)?,
+            prompt: row.get(4)?,
+            response: row.get(5)?,
+            temperature: row.get(6)?,
+            prompt_tokens: row.get(7)?,
+            completion_tokens: row.get(8)?,
+            duration_ms: row.get(9)?,
+            cost: row.get(10)?,
+        })
+    }
+
+    // "Groq:llama3" for display; unknown names are shown as stored
+    pub fn target(&self) -> String {
+        let provider = LlmProvider::from_config_name(&self.provider).map_or(self.provider.clone(), |p| p.to_string());
+        format!("{}:{}", provider, self.model)
+    }
+
+    // The first line of the prompt, shortened for listings
+    pub fn title(&self, max_chars: usize) -> String {
+        let line = self.prompt.lines().find(|l| !l.trim().is_empty()).unwrap_or("").trim();
+        if line.chars().count() > max_chars {
+            format!("{}...", line.chars().take(max_chars).collect::<String>())
+        } else {
+            line.to_string()
+        }
+    }
+
+    pub fn summary_line(&self) -> String {
+        let tokens = |t: Option<u32>| t.map_or_else(|| "?".to_string(), |t| t.to_string());
+        format!(
+            "tokens: {} in / {} out | {:.2}s{}",
+            tokens(self.prompt_tokens),
+            tokens(self.completion_tokens),
+            self.duration_ms as f64 / 1000.0,
+            self.cost.filter(|c| *c > 0.0).map_or_else(String::new, |c| format!(" | ${:.6}", c))
+        )
+    }
+}
+
+// A search hit with the matching part of the answer (or prompt)
+pub struct SearchHit {
+    pub exchange: Exchange,
+    pub snippet: String,
+}
+
+// --- Log Filter ---
+// Options for `llm-chat log`.
+#[derive(Parser, Debug, Clone, Default)]
+#[command(name = "log", about = "List recent prompts and answers from the history database")]
+pub struct LogArgs {
+    /// Only exchanges answered by this provider (ollama, gemini, groq, huggingface)
+    #[arg(long)]
+    pub provider: Option<String>,
+
+    /// Only exchanges whose model name contains this text
+    #[arg(long)]
+    pub model: Option<String>,
+
+    /// Only exchanges on or after this date (YYYY-MM-DD)
+    #[arg(long, value_name = "DATE")]
+    pub since: Option<NaiveDate>,
+
+    /// Only exchanges on or before this date (YYYY-MM-DD)
+    #[arg(long, value_name = "DATE")]
+    pub until: Option<NaiveDate>,
+
+    /// Number of exchanges to show
+    #[arg(short = 'n', long, default_value_t = 20)]
+    pub limit: usize,
+}
+
+// Unix time of local midnight starting `date`
+fn local_midnight(date: NaiveDate) -> Result<i64> {
+    let midnight = date.and_hms_opt(0, 0, 0).expect("midnight is valid");
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map(|t| t.timestamp())
+        .ok_or_else(|| anyhow!("Invalid local date {}", date))
+}
+
+// --- History Database ---
+// Every answered prompt, kept in <config dir>/history.db.
+#[derive(Debug)]
+pub struct History {
+    connection: Connection,
+}
+
+pub fn history_path() -> PathBuf {
+    config::config_dir().join(HISTORY_DB)
+}
+
+impl History {
+    pub fn open() -> Result<History> {
+        let path = history_path();
+        let connection = Connection::open(&path).context(format!("Failed to open history database {:?}", path))?;
+        connection.execute_batch(SCHEMA).context("Failed to create history tables")?;
+        Ok(History { connection })
+    }
+
+    pub fn record(
+        &self,
+        prompt: &str,
+        generation: &Generation,
+        temperature: Option<f32>,
+        cost: Option<f64>,
+    ) -> Result<i64> {
+        self.connection
+            .execute(
+                "INSERT INTO exchanges (timestamp, provider, model, prompt, response, temperature, \
+                 prompt_tokens, completion_tokens, duration_ms, cost) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![
+                    Local::now().timestamp(),
+                    generation.provider.get_provider_config_name(),
+                    generation.model,
+                    prompt,
+                    generation.text,
+                    temperature,
+                    generation.usage.prompt_tokens,
+                    generation.usage.completion_tokens,
+                    generation.usage.total_duration.as_millis() as u64,
+                    cost,
+                ],
+            )
+            .context("Failed to record exchange")?;
+        Ok(self.connection.last_insert_rowid())
+    }
+
+    pub fn get(&self, id: i64) -> Result<Option<Exchange>> {
+        self.connection
+            .query_row(&format!("SELECT {} FROM exchanges WHERE id = ?1", COLUMNS), [id], Exchange::from_row)
+            .optional()
+            .context("Failed to read exchange")
+    }
+
+    // Best matches first. Each word must appear (prefix match); FTS syntax in
+    // the input is treated as plain text.
+    pub fn search(&self, terms: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        let query = terms
+            .split_whitespace()
+            .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let columns = COLUMNS.split(", ").map(|c| format!("e.{}", c)).collect::<Vec<_>>().join(", ");
+        let sql = format!(
+            "SELECT {}, snippet(exchanges_fts, -1, '\x1b[1m', '\x1b[0m', '...', 16) \
+             FROM exchanges_fts JOIN exchanges e ON e.id = exchanges_fts.rowid \
+             WHERE exchanges_fts MATCH ?1 ORDER BY rank LIMIT ?2",
+            columns
+        );
+        let mut statement = self.connection.prepare(&sql)?;
+        let hits = statement
+            .query_map(params![query, limit as i64], |row| {
+                Ok(SearchHit { exchange: Exchange::from_row(row)?, snippet: row.get(11)? })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("History search failed")?;
+        Ok(hits)
+    }
+
+    // Newest first
+    pub fn recent(&self, filter: &LogArgs) -> Result<Vec<Exchange>> {
+        let since = filter.since.map(local_midnight).transpose()?;
+        let until = filter.until.and_then(|d| d.succ_opt()).map(local_midnight).transpose()?;
+        let provider = filter.provider.as_deref().map(|name| {
+            LlmProvider::from_config_name(name).map_or(name.to_lowercase(), |p| p.get_provider_config_name().to_string())
+        });
+        let sql = format!(
+            "SELECT {} FROM exchanges \
+             WHERE (?1 IS NULL OR provider = ?1) \
+             AND (?2 IS NULL OR instr(lower(model), lower(?2)) > 0) \
+             AND (?3 IS NULL OR timestamp >= ?3) \
+             AND (?4 IS NULL OR timestamp < ?4) \
+             ORDER BY timestamp DESC, id DESC LIMIT ?5",
+            COLUMNS
+        );
+        let mut statement = self.connection.prepare(&sql)?;
+        let exchanges = statement
+            .query_map(params![provider, filter.model, since, until, filter.limit as i64], Exchange::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Failed to list history")?;
+        Ok(exchanges)
+    }
+}
+
+// --- `llm-chat log` ---
+pub fn run_log(args: &LogArgs) -> Result<()> {
+    let history = History::open()?;
+    let exchanges = history.recent(args)?;
+    if exchanges.is_empty() {
+        println!("No matching exchanges in {}.", history_path().display());
+        return Ok(());
+    }
+    for exchange in exchanges.iter().rev() {
+        println!(
+            "#{:<5} {}  {}  {}",
+            exchange.id,
+            exchange.timestamp.format("%Y-%m-%d %H:%M"),
+            exchange.target(),
+            exchange.summary_line()
+        );
+        println!("       {}", exchange.title(100));
+    }
+    Ok(())
+}
@@ -0,0 +1,448 @@
+// src/import.rs
+use crate::config::LlmProvider;
+use crate::error::Result;
+use crate::llm::message::{AnswerMeta, Image, Message, Role, ToolCall};
+use crate::llm::openai_compatible::{ChatContent, ChatMessage, ContentPart};
+use crate::llm::usage::Usage;
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, Local, TimeZone};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+// --- Imported Conversations ---
+// One conversation read from another tool's export. Files with several
+// conversations (ChatGPT) yield one entry each.
+#[derive(Debug, Clone)]
+pub struct Imported {
+    pub title: Option<String>,
+    // Detected source format, for messages
+    pub format: &'static str,
+    pub messages: Vec<Message>,
+}
+
+// Detects the format from the contents:
+// - JSON array of messages, or an object with "messages" (OpenAI / /export json)
+// - JSON with "mapping" trees (ChatGPT conversations.json)
+// - Ollama Modelfile with MESSAGE (and SYSTEM) instructions
+pub fn read_file(path: &Path) -> Result<Vec<Imported>> {
+    let text = fs::read_to_string(path).context(format!("Failed to read {:?}", path))?;
+    let imported = match serde_json::from_str::<Value>(&text) {
+        Ok(json) => from_json(&json)?,
+        Err(_) if is_modelfile(&text) => vec![from_modelfile(&text)?],
+        Err(e) => return Err(anyhow!("{:?} is neither JSON nor a Modelfile: {}", path, e)),
+    };
+    let imported: Vec<Imported> = imported.into_iter().filter(|c| !c.messages.is_empty()).collect();
+    if imported.is_empty() {
+        return Err(anyhow!("No messages found in {:?}", path));
+    }
+    Ok(imported)
+}
+
+fn from_json(json: &Value) -> Result<Vec<Imported>> {
+    let is_chatgpt = |v: &Value| v.get("mapping").is_some();
+    match json {
+        Value::Array(items) if items.first().is_some_and(is_chatgpt) => items.iter().map(from_chatgpt).collect(),
+        Value::Array(_) => Ok(vec![from_openai(json, None)?]),
+        Value::Object(_) if is_chatgpt(json) => Ok(vec![from_chatgpt(json)?]),
+        Value::Object(object) => {
+            let messages = object.get("messages").ok_or_else(|| anyhow!("Expected a \"messages\" array"))?;
+            Ok(vec![from_openai(messages, object.get("metadata"))?])
+        }
+        _ => Err(anyhow!("Expected a JSON array or object")),
+    }
+}
+
+// --- OpenAI Messages ---
+// `metadata` is what /export json writes; it restores timestamps and
+// which model produced each answer.
+fn from_openai(messages: &Value, metadata: Option<&Value>) -> Result<Imported> {
+    let chat_messages: Vec<ChatMessage> =
+        serde_json::from_value(messages.clone()).context("Invalid OpenAI-style messages")?;
+    let mut imported = Vec::with_capacity(chat_messages.len());
+    for chat_message in chat_messages {
+        imported.push(message_from_chat(chat_message)?);
+    }
+
+    let message_meta = metadata.and_then(|m| m.get("messages")).and_then(Value::as_array);
+    for entry in message_meta.into_iter().flatten() {
+        let Some(message) = entry.get("index").and_then(Value::as_u64).and_then(|i| imported.get_mut(i as usize))
+        else {
+            continue;
+        };
+        message.timestamp = entry
+            .get("timestamp")
+            .and_then(Value::as_str)
+            .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+            .map(|t| t.with_timezone(&Local));
+        let provider = entry.get("provider").and_then(Value::as_str).and_then(LlmProvider::from_config_name);
+        let model = entry.get("model").and_then(Value::as_str);
+        if let (Some(provider), Some(model), Role::Assistant) = (provider, model, message.role) {
+            let tokens = |key: &str| entry.get(key).and_then(Value::as_u64).map(|t| t as u32);
+            let duration = entry.get("duration_secs").and_then(Value::as_f64).unwrap_or(0.0);
+            message.meta = Some(AnswerMeta {
+                provider,
+                model: model.to_string(),
+                temperature: entry.get("temperature").and_then(Value::as_f64).map(|t| t as f32),
+                usage: Usage {
+                    prompt_tokens: tokens("prompt_tokens"),
+                    completion_tokens: tokens("completion_tokens"),
+                    total_duration: Duration::from_secs_f64(duration.max(0.0)),
+                    ..Usage::default()
+                },
+            });
+        }
+    }
+    Ok(Imported { title: None, format: "OpenAI messages", messages: imported })
+}
+
+fn message_from_chat(chat_message: ChatMessage) -> Result<Message> {
+    let role = parse_role(&chat_message.role)?;
+    let mut text = Vec::new();
+    let mut images = Vec::new();
+    match chat_message.content {
+        None => {}
+        Some(ChatContent::Text(content)) => text.push(content),
+        Some(ChatContent::Parts(parts)) => {
+            for part in parts {
+                match part {
+                    ContentPart::Text { text: part } => text.push(part),
+                    // Only inline images can be sent again; keep links as text
+                    ContentPart::ImageUrl { image_url } => match image_from_data_uri(&image_url.url) {
+                        Some(image) => images.push(image),
+                        None => text.push(format!("[image: {}]", image_url.url)),
+                    },
+                }
+            }
+        }
+    }
+    let tool_calls: Vec<ToolCall> = chat_message
+        .tool_calls
+        .iter()
+        .map(|call| ToolCall {
+            id: call.id.clone(),
+            name: call.function.name.clone(),
+            arguments: serde_json::from_str(&call.function.arguments)
+                .unwrap_or_else(|_| Value::String(call.function.arguments.clone())),
+        })
+        .collect();
+
+    Ok(Message {
+        role,
+        images,
+        tool_calls,
+        tool_call_id: chat_message.tool_call_id,
+        timestamp: None,
+        ..Message::user(text.join("\n"))
+    })
+}
+
+fn parse_role(role: &str) -> Result<Role> {
+    match role {
+        "system" | "developer" => Ok(Role::System),
+        "user" => Ok(Role::User),
+        "assistant" => Ok(Role::Assistant),
+        "tool" => Ok(Role::Tool),
+        other => Err(anyhow!("Unsupported message role '{}'", other)),
+    }
+}
+
+fn image_from_data_uri(uri: &str) -> Option<Image> {
+    let (header, data) = uri.strip_prefix("data:")?.split_once(',')?;
+    let mime_type = header.strip_suffix(";base64")?;
+    Some(Image { mime_type: mime_type.to_string(), data: data.to_string() })
+}
+
+// --- ChatGPT conversations.json ---
+// Each conversation is a tree of nodes; the shown thread is the path from
+// `current_node` up to the root. Tool/browsing steps and hidden system
+// messages are skipped since they cannot be replayed to another model.
+#[derive(Debug, Deserialize)]
+struct ChatGptConversation {
+    title: Option<String>,
+    mapping: HashMap<String, ChatGptNode>,
+    current_node: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatGptNode {
+    message: Option<ChatGptMessage>,
+    parent: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatGptMessage {
+    author: ChatGptAuthor,
+    content: Value,
+    create_time: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatGptAuthor {
+    role: String,
+}
+
+fn from_chatgpt(json: &Value) -> Result<Imported> {
+    let conversation: ChatGptConversation =
+        serde_json::from_value(json.clone()).context("Invalid ChatGPT conversation")?;
+    let mut thread = Vec::new();
+    let mut node_id = conversation.current_node.clone();
+    while let Some(node) = node_id.as_ref().and_then(|id| conversation.mapping.get(id)) {
+        if thread.len() > conversation.mapping.len() {
+            return Err(anyhow!("Conversation '{}' has a cycle", conversation.title.unwrap_or_default()));
+        }
+        thread.push(node);
+        node_id = node.parent.clone();
+    }
+    thread.reverse();
+
+    let mut messages: Vec<Message> = Vec::new();
+    for chatgpt_message in thread.into_iter().filter_map(|node| node.message.as_ref()) {
+        let role = match chatgpt_message.author.role.as_str() {
+            "system" => Role::System,
+            "user" => Role::User,
+            "assistant" => Role::Assistant,
+            _ => continue,
+        };
+        let text = chatgpt_text(&chatgpt_message.content);
+        if text.trim().is_empty() {
+            continue;
+        }
+        let timestamp = chatgpt_message.create_time.and_then(|t| Local.timestamp_opt(t as i64, 0).single());
+        // Skipped steps can leave two answers in a row; keep them as one
+        match messages.last_mut() {
+            Some(last) if last.role == role => {
+                last.content.push_str("\n\n");
+                last.content.push_str(&text);
+            }
+            _ => {
+                let mut message = match role {
+                    Role::System => Message::system(text),
+                    Role::User => Message::user(text),
+                    _ => Message::assistant(text),
+                };
+                message.timestamp = timestamp;
+                messages.push(message);
+            }
+        }
+    }
+    Ok(Imported { title: conversation.title, format: "ChatGPT export", messages })
+}
+
+// Text parts of a message; non-text parts (images, files) are dropped
+fn chatgpt_text(content: &Value) -> String {
+    if let Some(parts) = content.get("parts").and_then(Value::as_array) {
+        return parts.iter().filter_map(Value::as_str).collect::<Vec<_>>().join("\n");
+    }
+    content.get("text").and_then(Value::as_str).unwrap_or_default().to_string()
+}
+
+// --- Ollama Modelfile ---
+// `MESSAGE <role> <text>` lines (plus SYSTEM) in order; values may be quoted
+// with "..." or span lines with """...""".
+fn is_modelfile(text: &str) -> bool {
+    text.lines().any(|line| {
+        let keyword = line.split_whitespace().next().unwrap_or("");
+        keyword.eq_ignore_ascii_case("MESSAGE") || keyword.eq_ignore_ascii_case("FROM")
+    })
+}
+
+fn from_modelfile(text: &str) -> Result<Imported> {
+    let mut system = None;
+    let mut messages = Vec::new();
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        let line = line.trim_start();
+        let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        if keyword.eq_ignore_ascii_case("SYSTEM") {
+            system = Some(modelfile_value(rest.trim_start(), &mut lines)?);
+        } else if keyword.eq_ignore_ascii_case("MESSAGE") {
+            let rest = rest.trim_start();
+            let (role, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            let content = modelfile_value(value.trim_start(), &mut lines)?;
+            let mut message = match role.to_lowercase().as_str() {
+                "system" => Message::system(content),
+                "user" => Message::user(content),
+                "assistant" => Message::assistant(content),
+                other => return Err(anyhow!("Unsupported MESSAGE role '{}'", other)),
+            };
+            message.timestamp = None;
+            messages.push(message);
+        }
+    }
+    if let Some(system) = system {
+        let mut message = Message::system(system);
+        message.timestamp = None;
+        messages.insert(0, message);
+    }
+    Ok(Imported { title: None, format: "Ollama Modelfile", messages })
+}
+
+fn modelfile_value<'a>(value: &str, lines: &mut impl Iterator<Item = &'a str>) -> Result<String> {
+    if let Some(rest) = value.strip_prefix("\"\"\"") {
+        if let Some(end) = rest.find("\"\"\"") {
+            return Ok(rest[..end].to_string());
+        }
+        let mut collected = vec![rest.to_string()];
+        for line in lines.by_ref() {
+            if let Some(end) = line.find("\"\"\"") {
+                collected.push(line[..end].to_string());
+                return Ok(collected.join("\n").trim().to_string());
+            }
+            collected.push(line.to_string());
+        }
+        return Err(anyhow!("Unclosed \"\"\" in Modelfile"));
+    }
+    let unquoted = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+    Ok(unquoted.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{self, ExportFormat, ExportInfo};
+    use crate::llm::usage::UsageTotals;
+    use serde_json::json;
+
+    #[test]
+    fn modelfile_messages_follow_the_system_prompt() {
+        let modelfile = "FROM llama3\n\
+                         PARAMETER temperature 0.2\n\
+                         MESSAGE user \"What is Rust?\"\n\
+                         MESSAGE assistant \"\"\"A language\n\
+                         \n\
+                         with a borrow checker.\"\"\"\n\
+                         SYSTEM You are terse.\n";
+        assert!(is_modelfile(modelfile));
+        let imported = from_modelfile(modelfile).unwrap();
+        let messages: Vec<(Role, &str)> = imported.messages.iter().map(|m| (m.role, m.content.as_str())).collect();
+        assert_eq!(
+            messages,
+            [
+                (Role::System, "You are terse."),
+                (Role::User, "What is Rust?"),
+                (Role::Assistant, "A language\n\nwith a borrow checker."),
+            ]
+        );
+        assert!(imported.messages.iter().all(|m| m.timestamp.is_none()));
+    }
+
+    #[test]
+    fn modelfile_rejects_unknown_roles_and_unclosed_quotes() {
+        assert!(from_modelfile("MESSAGE tool \"hi\"").is_err());
+        assert!(from_modelfile("MESSAGE user \"\"\"never closed\nstill open").is_err());
+    }
+
+    #[test]
+    fn chatgpt_follows_the_current_thread() {
+        let conversation = json!({
+            "title": "Borrowing",
+            "current_node": "answer",
+            "mapping": {
+                "root": { "message": null, "parent": null },
+                "hidden": {
+                    "message": { "author": { "role": "system" }, "content": { "parts": [""] } },
+                    "parent": "root"
+                },
+                "question": {
+                    "message": {
+                        "author": { "role": "user" },
+                        "content": { "content_type": "text", "parts": ["Why does this not compile?"] },
+                        "create_time": 1_700_000_000.5
+                    },
+                    "parent": "hidden"
+                },
+                "search": {
+                    "message": { "author": { "role": "tool" }, "content": { "parts": ["search results"] } },
+                    "parent": "question"
+                },
+                "draft": {
+                    "message": { "author": { "role": "assistant" }, "content": { "parts": ["It moves the value."] } },
+                    "parent": "search"
+                },
+                "answer": {
+                    "message": { "author": { "role": "assistant" }, "content": { "text": "Borrow it instead." } },
+                    "parent": "draft"
+                },
+                "abandoned": {
+                    "message": { "author": { "role": "assistant" }, "content": { "parts": ["An older answer"] } },
+                    "parent": "question"
+                }
+            }
+        });
+        let imported = from_chatgpt(&conversation).unwrap();
+        assert_eq!(imported.title.as_deref(), Some("Borrowing"));
+        let messages: Vec<(Role, &str)> = imported.messages.iter().map(|m| (m.role, m.content.as_str())).collect();
+        assert_eq!(
+            messages,
+            [
+                (Role::User, "Why does this not compile?"),
+                (Role::Assistant, "It moves the value.\n\nBorrow it instead."),
+            ]
+        );
+        assert_eq!(imported.messages[0].timestamp.map(|t| t.timestamp()), Some(1_700_000_000));
+    }
+
+    #[test]
+    fn chatgpt_rejects_cycles() {
+        let conversation = json!({
+            "title": "Loop",
+            "current_node": "a",
+            "mapping": {
+                "a": { "message": null, "parent": "b" },
+                "b": { "message": null, "parent": "a" }
+            }
+        });
+        assert!(from_chatgpt(&conversation).is_err());
+    }
+
+    #[test]
+    fn json_export_imports_back() {
+        let mut answer = Message::assistant("Use `&value`.");
+        answer.meta = Some(AnswerMeta {
+            provider: LlmProvider::Groq,
+            model: "llama3-8b-8192".to_string(),
+            temperature: Some(0.3),
+            usage: Usage {
+                prompt_tokens: Some(12),
+                completion_tokens: Some(5),
+                total_duration: Duration::from_millis(1500),
+                ..Usage::default()
+            },
+        });
+        let messages = vec![Message::system("Be brief."), Message::user("How do I borrow?"), answer];
+        let usage = UsageTotals::default();
+        let info = ExportInfo {
+            provider: &LlmProvider::Groq,
+            model: "llama3-8b-8192",
+            parameters: Vec::new(),
+            usage: &usage,
+            cost: 0.0,
+            branch: 0,
+            exported_at: Local::now(),
+        };
+        let path = std::env::temp_dir().join(format!("llm-chat-import-test-{}.json", std::process::id()));
+        export::write(ExportFormat::Json, &path, &messages, &info).unwrap();
+        let imported = read_file(&path);
+        fs::remove_file(&path).unwrap();
+
+        let imported = imported.unwrap();
+        assert_eq!(imported.len(), 1);
+        let restored = &imported[0].messages;
+        assert_eq!(restored.len(), messages.len());
+        for (original, restored) in messages.iter().zip(restored) {
+            assert_eq!(original.role, restored.role);
+            assert_eq!(original.content, restored.content);
+            assert_eq!(original.timestamp, restored.timestamp);
+        }
+        let meta = restored[2].meta.as_ref().expect("answer metadata");
+        assert_eq!(meta.provider, LlmProvider::Groq);
+        assert_eq!(meta.model, "llama3-8b-8192");
+        assert_eq!(meta.temperature, Some(0.3));
+        assert_eq!((meta.usage.prompt_tokens, meta.usage.completion_tokens), (Some(12), Some(5)));
+        assert_eq!(meta.usage.total_duration, Duration::from_millis(1500));
+    }
+}
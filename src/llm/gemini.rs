@@ -1,10 +1,14 @@
 // src/llm/gemini.rs
 
 use crate::config::Config;
-use crate::error::Result;
+use crate::error::{ApiStatusError, Result};
+use crate::llm::message::{Message, Role, ToolCall};
+use crate::llm::usage::Usage;
+use crate::llm::{Completion, RequestOptions};
 use anyhow::{anyhow, Context};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::time::Instant;
 use tracing::{debug, error, instrument, warn};
 
 // --- Request Structs ---
@@ -12,18 +16,75 @@ use tracing::{debug, error, instrument, warn};
 #[derive(Serialize, Debug)]
 struct GeminiRequest {
     contents: Vec<Content>,
+    #[serde(rename = "systemInstruction", skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<Content>,
     #[serde(rename = "generationConfig", skip_serializing_if = "Option::is_none")]
     generation_config: Option<GenerationConfig>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<GeminiTool>,
 }
 
 #[derive(Serialize, Debug)]
 struct Content {
+    // "user" or "model"; omitted for the system instruction
+    #[serde(skip_serializing_if = "Option::is_none")]
+    role: Option<String>,
     parts: Vec<Part>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 struct Part {
-    text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(rename = "inlineData", skip_serializing_if = "Option::is_none")]
+    inline_data: Option<InlineData>,
+    #[serde(rename = "functionCall", skip_serializing_if = "Option::is_none")]
+    function_call: Option<FunctionCall>,
+    #[serde(rename = "functionResponse", skip_serializing_if = "Option::is_none")]
+    function_response: Option<FunctionResponse>,
+}
+
+impl Part {
+    fn text(text: impl Into<String>) -> Self {
+        Part { text: Some(text.into()), ..Part::default() }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct InlineData {
+    #[serde(rename = "mimeType")]
+    mime_type: String,
+    // Base64-encoded bytes
+    data: String,
+}
+
+// --- Tool Calling Structs ---
+
+#[derive(Serialize, Debug)]
+struct GeminiTool {
+    #[serde(rename = "functionDeclarations")]
+    function_declarations: Vec<FunctionDeclaration>,
+}
+
+#[derive(Serialize, Debug)]
+struct FunctionDeclaration {
+    name: String,
+    description: String,
+    parameters: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct FunctionCall {
+    name: String,
+    #[serde(default)]
+    args: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct FunctionResponse {
+    name: String,
+    // Must be a JSON object
+    response: serde_json::Value,
 }
 
 #[derive(Serialize, Debug, Default)]
@@ -34,6 +95,10 @@ struct GenerationConfig {
     top_p: Option<f32>,
     #[serde(rename = "maxOutputTokens", skip_serializing_if = "Option::is_none")]
     max_output_tokens: Option<u32>,
+    #[serde(rename = "responseMimeType", skip_serializing_if = "Option::is_none")]
+    response_mime_type: Option<String>,
+    #[serde(rename = "responseSchema", skip_serializing_if = "Option::is_none")]
+    response_schema: Option<serde_json::Value>,
 }
 
 // --- Response Structs ---
@@ -43,9 +108,19 @@ struct GeminiResponse {
     candidates: Option<Vec<Candidate>>,
     #[serde(rename = "promptFeedback")]
     prompt_feedback: Option<PromptFeedback>,
+    #[serde(rename = "usageMetadata")]
+    usage_metadata: Option<UsageMetadata>,
     error: Option<ApiError>,
 }
 
+#[derive(Deserialize, Debug)]
+struct UsageMetadata {
+    #[serde(rename = "promptTokenCount")]
+    prompt_token_count: Option<u32>,
+    #[serde(rename = "candidatesTokenCount")]
+    candidates_token_count: Option<u32>,
+}
+
 #[derive(Deserialize, Debug)]
 struct Candidate {
     content: Option<ContentResponse>,
@@ -55,6 +130,7 @@ struct Candidate {
     safety_ratings: Option<Vec<SafetyRating>>,
 }
 
+#[allow(dead_code)] // Mirrors the API; not every field is read
 #[derive(Deserialize, Debug)]
 struct ContentResponse {
     parts: Option<Vec<Part>>,
@@ -69,6 +145,7 @@ struct PromptFeedback {
     safety_ratings: Option<Vec<SafetyRating>>,
 }
 
+#[allow(dead_code)] // Mirrors the API; not every field is read
 #[derive(Deserialize, Debug)]
 struct SafetyRating {
     category: String,
@@ -82,12 +159,44 @@ struct ApiError {
     status: String,
 }
 
+// --- Embedding Structs ---
+
+#[derive(Serialize, Debug)]
+struct EmbedContentRequest {
+    // "models/<name>"; required per request in batches
+    model: String,
+    content: Content,
+}
+
+#[derive(Serialize, Debug)]
+struct BatchEmbedContentsRequest {
+    requests: Vec<EmbedContentRequest>,
+}
+
+#[derive(Deserialize, Debug)]
+struct EmbedContentResponse {
+    embedding: Option<ContentEmbedding>,
+    error: Option<ApiError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct BatchEmbedContentsResponse {
+    embeddings: Option<Vec<ContentEmbedding>>,
+    error: Option<ApiError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ContentEmbedding {
+    values: Vec<f32>,
+}
+
 #[derive(Deserialize, Debug)]
 struct GeminiListModelsResponse {
     models: Option<Vec<GeminiModelInfo>>,
     error: Option<ApiError>,
 }
 
+#[allow(dead_code)] // Mirrors the API; not every field is read
 #[derive(Deserialize, Debug)]
 struct GeminiModelInfo {
     name: String,
@@ -96,6 +205,8 @@ struct GeminiModelInfo {
     version: Option<String>,
     #[serde(rename = "supportedGenerationMethods")]
     supported_generation_methods: Option<Vec<String>>,
+    #[serde(rename = "inputTokenLimit")]
+    input_token_limit: Option<u32>,
 }
 
 // --- Helper function to handle API responses ---
@@ -120,19 +231,22 @@ async fn handle_api_response<T: serde::de::DeserializeOwned + std::fmt::Debug>(
                 response_body = ?body_string,
                 "Failed to parse {} response", operation_name
             );
-            let base_msg = if status.is_success() { format!("Failed to parse successful {} response", operation_name) } else { format!("API {} request failed", operation_name) };
-            Err(anyhow!("{} (Status: {}): {}. Body: {}", base_msg, status, parse_error, body_string))
+            if !status.is_success() {
+                return Err(ApiStatusError { status: status.as_u16(), message: format!("{} request failed: {}", operation_name, body_string) }.into());
+            }
+            Err(anyhow!("Failed to parse successful {} response (Status: {}): {}. Body: {}", operation_name, status, parse_error, body_string))
         }
     }
 }
 
 // --- generate function ---
-#[instrument(skip(client, config, prompt))]
+#[instrument(skip(client, config, messages, options))]
 pub async fn generate(
     client: &Client,
     config: &Config,
-    prompt: &str,
-) -> Result<String> {
+    messages: &[Message],
+    options: &RequestOptions<'_>,
+) -> Result<Completion> {
     let api_key = config.gemini_api_key.as_deref().ok_or_else(|| anyhow!("GEMINI_API_KEY is not set."))?;
     let model_name = &config.default_gemini_model;
     let url = format!(
@@ -142,16 +256,52 @@ pub async fn generate(
 
     let mut gen_config = GenerationConfig::default();
     let mut config_set = false;
-    if config.gemini_temperature.is_some() { gen_config.temperature = config.gemini_temperature; config_set = true; }
+    let temperature = options.temperature.or(config.gemini_temperature);
+    if temperature.is_some() { gen_config.temperature = temperature; config_set = true; }
     if config.gemini_top_p.is_some() { gen_config.top_p = config.gemini_top_p; config_set = true; }
     if config.gemini_max_tokens.is_some() { gen_config.max_output_tokens = config.gemini_max_tokens; config_set = true; }
+    if let Some(schema) = options.response_schema {
+        gen_config.response_mime_type = Some("application/json".to_string());
+        gen_config.response_schema = Some(gemini_schema(schema));
+        config_set = true;
+    }
+
+    // Gemini takes system prompts separately and calls the assistant "model"
+    let system_text: Vec<&str> = messages.iter()
+        .filter(|m| m.role == Role::System)
+        .map(|m| m.content.as_str())
+        .collect();
+    let system_instruction = if system_text.is_empty() {
+        None
+    } else {
+        Some(Content { role: None, parts: vec![Part::text(system_text.join("\n\n"))] })
+    };
+    let contents = build_contents(messages);
+    let tools = if options.tools.is_empty() {
+        Vec::new()
+    } else {
+        vec![GeminiTool {
+            function_declarations: options
+                .tools
+                .iter()
+                .map(|tool| FunctionDeclaration {
+                    name: tool.name.clone(),
+                    description: tool.description.clone(),
+                    parameters: gemini_schema(&tool.parameters),
+                })
+                .collect(),
+        }]
+    };
 
     let request_payload = GeminiRequest {
-        contents: vec![Content { parts: vec![Part { text: prompt.to_string(), }], }],
+        contents,
+        system_instruction,
         generation_config: if config_set { Some(gen_config) } else { None },
+        tools,
     };
 
     debug!(?url, ?request_payload, "Sending generate request to Gemini API");
+    let start = Instant::now();
     let response = client.post(&url).json(&request_payload).send().await.context("Failed to send generate request to Gemini API")?;
 
     // Use the helper function to handle the response
@@ -160,7 +310,7 @@ pub async fn generate(
     // Handle top-level API errors first
     if let Some(api_error) = gemini_response.error {
         error!(?api_error, "Gemini API returned an error in the response body");
-        return Err(anyhow!("Gemini API Error ({} {}): {}", api_error.code, api_error.status, api_error.message));
+        return Err(ApiStatusError { status: api_error.code, message: format!("Gemini API Error ({}): {}", api_error.status, api_error.message) }.into());
     }
 
     // Check prompt feedback for blocking
@@ -207,14 +357,136 @@ pub async fn generate(
             anyhow!("Content 'parts' are missing in Gemini response")
         })?;
 
-    let text = parts.first()
-        .ok_or_else(|| {
-            error!(?parts, "Content parts array is empty.");
-            anyhow!("Content parts array is empty in Gemini response")
-        })?
-        .text.clone();
+    if parts.is_empty() {
+        error!(?parts, "Content parts array is empty.");
+        return Err(anyhow!("Content parts array is empty in Gemini response"));
+    }
+    let text = parts.iter().filter_map(|p| p.text.as_deref()).collect::<Vec<_>>().join("");
+    // Gemini does not assign call ids, so number them
+    let tool_calls = parts
+        .iter()
+        .filter_map(|p| p.function_call.as_ref())
+        .enumerate()
+        .map(|(i, call)| ToolCall { id: format!("call_{}", i), name: call.name.clone(), arguments: call.args.clone() })
+        .collect();
+
+    let elapsed = start.elapsed();
+    let metadata = gemini_response.usage_metadata.as_ref();
+    let usage = Usage {
+        prompt_tokens: metadata.and_then(|m| m.prompt_token_count),
+        completion_tokens: metadata.and_then(|m| m.candidates_token_count),
+        total_duration: elapsed,
+        time_to_first_token: Some(elapsed),
+        tokens_per_sec: None,
+    }
+    .with_rate(elapsed);
+
+    Ok(Completion { text, usage, tool_calls })
+}
+
+// responseSchema and function parameters take an OpenAPI-style subset of
+// JSON schema; drop the keywords it rejects (local validation still checks
+// the full schema). Names under "properties" are fields, not keywords.
+fn gemini_schema(schema: &serde_json::Value) -> serde_json::Value {
+    const UNSUPPORTED: [&str; 6] = ["$schema", "$id", "$defs", "additionalProperties", "default", "examples"];
+    match schema {
+        serde_json::Value::Object(map) => map
+            .iter()
+            .filter(|(key, _)| !UNSUPPORTED.contains(&key.as_str()))
+            .map(|(key, value)| match (key.as_str(), value) {
+                ("properties", serde_json::Value::Object(properties)) => (
+                    key.clone(),
+                    properties.iter().map(|(name, property)| (name.clone(), gemini_schema(property))).collect(),
+                ),
+                _ => (key.clone(), gemini_schema(value)),
+            })
+            .collect(),
+        serde_json::Value::Array(items) => items.iter().map(gemini_schema).collect(),
+        other => other.clone(),
+    }
+}
+
+// Maps the conversation (minus system prompts) onto Gemini contents. The
+// assistant is called "model", and tool results are sent back as user turns
+// with functionResponse parts; consecutive results share one turn.
+fn build_contents(messages: &[Message]) -> Vec<Content> {
+    let mut contents: Vec<Content> = Vec::new();
+    for m in messages.iter().filter(|m| m.role != Role::System) {
+        match m.role {
+            Role::Tool => {
+                let part = Part {
+                    function_response: Some(FunctionResponse {
+                        name: m.tool_name.clone().unwrap_or_default(),
+                        response: serde_json::json!({ "content": m.content }),
+                    }),
+                    ..Part::default()
+                };
+                match contents.last_mut() {
+                    Some(last) if last.parts.iter().all(|p| p.function_response.is_some()) => last.parts.push(part),
+                    _ => contents.push(Content { role: Some("user".to_string()), parts: vec![part] }),
+                }
+            }
+            Role::Assistant => {
+                let mut parts = Vec::new();
+                if !m.content.is_empty() || m.tool_calls.is_empty() {
+                    parts.push(Part::text(m.content.clone()));
+                }
+                parts.extend(m.tool_calls.iter().map(|call| Part {
+                    function_call: Some(FunctionCall { name: call.name.clone(), args: call.arguments.clone() }),
+                    ..Part::default()
+                }));
+                contents.push(Content { role: Some("model".to_string()), parts });
+            }
+            _ => {
+                let mut parts = vec![Part::text(m.content.clone())];
+                parts.extend(m.images.iter().map(|image| Part {
+                    inline_data: Some(InlineData { mime_type: image.mime_type.clone(), data: image.data.clone() }),
+                    ..Part::default()
+                }));
+                contents.push(Content { role: Some("user".to_string()), parts });
+            }
+        }
+    }
+    contents
+}
+
+// --- embed function ---
+// A single input uses embedContent, several use batchEmbedContents.
+#[instrument(skip(client, config, inputs))]
+pub async fn embed(client: &Client, config: &Config, model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>> {
+    let api_key = config.gemini_api_key.as_deref().ok_or_else(|| anyhow!("GEMINI_API_KEY is not set."))?;
+    let model_path = if model.starts_with("models/") { model.to_string() } else { format!("models/{}", model) };
+    let request_for = |text: &String| EmbedContentRequest {
+        model: model_path.clone(),
+        content: Content { role: None, parts: vec![Part::text(text.clone())] },
+    };
+
+    if let [only] = inputs {
+        let url = format!(
+            "https://generativelanguage.googleapis.com/v1beta/{}:embedContent?key={}",
+            model_path, api_key
+        );
+        let response = client.post(&url).json(&request_for(only)).send().await.context("Failed to send embed request to Gemini API")?;
+        let embed_response: EmbedContentResponse = handle_api_response(response, &url, "Gemini embed").await?;
+        if let Some(api_error) = embed_response.error {
+            return Err(ApiStatusError { status: api_error.code, message: format!("Gemini API Error ({}): {}", api_error.status, api_error.message) }.into());
+        }
+        let embedding = embed_response.embedding.ok_or_else(|| anyhow!("No embedding in Gemini response"))?;
+        return Ok(vec![embedding.values]);
+    }
 
-    Ok(text)
+    let url = format!(
+        "https://generativelanguage.googleapis.com/v1beta/{}:batchEmbedContents?key={}",
+        model_path, api_key
+    );
+    let request_payload = BatchEmbedContentsRequest { requests: inputs.iter().map(request_for).collect() };
+    let response = client.post(&url).json(&request_payload).send().await.context("Failed to send batch embed request to Gemini API")?;
+    let batch_response: BatchEmbedContentsResponse = handle_api_response(response, &url, "Gemini batch embed").await?;
+    if let Some(api_error) = batch_response.error {
+        return Err(ApiStatusError { status: api_error.code, message: format!("Gemini API Error ({}): {}", api_error.status, api_error.message) }.into());
+    }
+    let embeddings = batch_response.embeddings.ok_or_else(|| anyhow!("No embeddings in Gemini response"))?;
+    Ok(embeddings.into_iter().map(|e| e.values).collect())
 }
 
 // --- list_models function ---
@@ -232,7 +504,7 @@ pub async fn list_models(client: &Client, config: &Config) -> Result<Vec<String>
     // Handle top-level API errors first
     if let Some(api_error) = list_response.error {
         error!(?api_error, "Gemini API returned an error listing models");
-        return Err(anyhow!("Gemini API Error listing models ({} {}): {}", api_error.code, api_error.status, api_error.message));
+        return Err(ApiStatusError { status: api_error.code, message: format!("Gemini API Error listing models ({}): {}", api_error.status, api_error.message) }.into());
     }
 
     let models = list_response.models.unwrap_or_default();
@@ -246,6 +518,21 @@ pub async fn list_models(client: &Client, config: &Config) -> Result<Vec<String>
     Ok(chat_model_ids)
 }
 
+// --- context_length function ---
+#[instrument(skip(client, config))]
+pub async fn context_length(client: &Client, config: &Config) -> Result<Option<u32>> {
+    let api_key = config.gemini_api_key.as_deref().ok_or_else(|| anyhow!("GEMINI_API_KEY is not set. Cannot fetch model info."))?;
+    let url = format!(
+        "https://generativelanguage.googleapis.com/v1beta/models/{}?key={}",
+        config.default_gemini_model, api_key
+    );
+
+    let response = client.get(&url).send().await.context("Failed to send model info request to Gemini API")?;
+    // Error bodies fail to parse as model info and surface as ApiStatusError
+    let model_info: GeminiModelInfo = handle_api_response(response, &url, "Gemini model info").await?;
+    Ok(model_info.input_token_limit)
+}
+
 // --- check_connection function ---
 #[instrument(skip(client, config))]
 pub async fn check_connection(client: &Client, config: &Config) -> Result<()> {
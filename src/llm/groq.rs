@@ -3,17 +3,20 @@
 use crate::config::Config;
 use crate::error::Result;
 use crate::llm::openai_compatible as common_client; // Use the shared client
+use crate::llm::message::Message;
+use crate::llm::{Completion, RequestOptions};
 use anyhow::{anyhow, Context};
 use reqwest::Client;
 use tracing::instrument;
 
 // --- Generate Function (using common client) ---
-#[instrument(skip(client, config, prompt))]
+#[instrument(skip(client, config, messages, options))]
 pub async fn generate(
     client: &Client,
     config: &Config,
-    prompt: &str,
-) -> Result<String> {
+    messages: &[Message],
+    options: &RequestOptions<'_>,
+) -> Result<Completion> {
     let api_key = config.groq_api_key.as_deref()
         .ok_or_else(|| anyhow!("GROQ_API_KEY is not set. Use '/config' or set environment variable."))?;
 
@@ -22,12 +25,23 @@ pub async fn generate(
         api_key,
         &config.groq_api_base_url,
         &config.default_groq_model,
-        prompt,
+        messages,
+        options,
         // Pass other Groq-specific params here if needed in common_client::generate
     )
     .await.context("Groq API generate call failed")
 }
 
+// --- Embed Function (using common client) ---
+#[instrument(skip(client, config, inputs))]
+pub async fn embed(client: &Client, config: &Config, model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>> {
+    let api_key = config.groq_api_key.as_deref()
+        .ok_or_else(|| anyhow!("GROQ_API_KEY is not set. Cannot create embeddings."))?;
+
+    common_client::embed(client, api_key, &config.groq_api_base_url, model, inputs)
+        .await.context("Groq API embeddings call failed")
+}
+
 // --- List Models Function (using common client) ---
 #[instrument(skip(client, config))]
 pub async fn list_models(client: &Client, config: &Config) -> Result<Vec<String>> {
@@ -38,6 +52,16 @@ pub async fn list_models(client: &Client, config: &Config) -> Result<Vec<String>
         .await.context("Groq API list models call failed")
 }
 
+// --- Context Length Function (using common client) ---
+#[instrument(skip(client, config))]
+pub async fn context_length(client: &Client, config: &Config) -> Result<Option<u32>> {
+    let api_key = config.groq_api_key.as_deref()
+        .ok_or_else(|| anyhow!("GROQ_API_KEY is not set. Cannot fetch model info."))?;
+
+    common_client::context_length(client, api_key, &config.groq_api_base_url, &config.default_groq_model)
+        .await.context("Groq API model info call failed")
+}
+
 // --- Check Connection Function (using common client) ---
 #[instrument(skip(client, config))]
 pub async fn check_connection(client: &Client, config: &Config) -> Result<()> {
@@ -1,10 +1,15 @@
 // src/llm/huggingface.rs
 
 use crate::config::Config;
-use crate::error::Result;
+use crate::error::{ApiStatusError, Result};
+use crate::llm::message::{Message, Role, ToolCall};
+use crate::llm::usage::Usage;
+use crate::llm::{Completion, RequestOptions};
+use crate::tools::ToolSpec;
 use anyhow::{anyhow, Context};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::time::Instant;
 use tracing::{debug, error, instrument};
 
 // --- Request Structs ---
@@ -12,7 +17,14 @@ use tracing::{debug, error, instrument};
 #[derive(Serialize, Debug)]
 struct HuggingFaceRequest {
     inputs: String,
-    // Add other parameters if needed (temperature, max_length, etc.)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parameters: Option<HuggingFaceParameters>,
+    // Add other parameters if needed (max_length, etc.)
+}
+
+#[derive(Serialize, Debug)]
+struct HuggingFaceParameters {
+    temperature: f32,
 }
 
 // --- Response Structs ---
@@ -46,29 +58,98 @@ async fn handle_api_response<T: serde::de::DeserializeOwned + std::fmt::Debug>(
                 response_body = ?body_string,
                 "Failed to parse {} response", operation_name
             );
-            let base_msg = if status.is_success() { format!("Failed to parse successful {} response", operation_name) } else { format!("API {} request failed", operation_name) };
-            Err(anyhow!("{} (Status: {}): {}. Body: {}", base_msg, status, parse_error, body_string))
+            if !status.is_success() {
+                return Err(ApiStatusError { status: status.as_u16(), message: format!("{} request failed: {}", operation_name, body_string) }.into());
+            }
+            Err(anyhow!("Failed to parse successful {} response (Status: {}): {}. Body: {}", operation_name, status, parse_error, body_string))
+        }
+    }
+}
+
+// --- Prompted Tool Calls ---
+// The text-generation endpoint has no tool support, so tools are described in
+// the prompt and calls are read back from a fenced block in the reply.
+const TOOL_CALL_FENCE: &str = "```tool-call";
+
+#[derive(Deserialize)]
+struct PromptedToolCall {
+    name: String,
+    #[serde(default)]
+    arguments: serde_json::Value,
+}
+
+fn tool_instructions(tools: &[ToolSpec]) -> String {
+    let mut text = String::from(
+        "system: You can call the following tools. To call one, reply with exactly one block:\n\
+         ```tool-call\n{\"name\": \"<tool>\", \"arguments\": {...}}\n```\n\
+         and nothing after it. You will receive the result and can then answer.\n",
+    );
+    for tool in tools {
+        text.push_str(&format!("- {}: {} Parameters: {}\n", tool.name, tool.description, tool.parameters));
+    }
+    text
+}
+
+fn parse_tool_call(text: &str) -> Option<ToolCall> {
+    let start = text.find(TOOL_CALL_FENCE)? + TOOL_CALL_FENCE.len();
+    let body = &text[start..];
+    let end = body.find("```")?;
+    let call: PromptedToolCall = serde_json::from_str(body[..end].trim()).ok()?;
+    Some(ToolCall { id: "call_0".to_string(), name: call.name, arguments: call.arguments })
+}
+
+fn format_message(m: &Message) -> String {
+    match m.role {
+        Role::Tool => format!("tool result ({}): {}\n", m.tool_name.as_deref().unwrap_or("unknown"), m.content),
+        Role::Assistant if !m.tool_calls.is_empty() => {
+            let calls: String = m
+                .tool_calls
+                .iter()
+                .map(|c| format!("{}\n{}\n```\n", TOOL_CALL_FENCE, serde_json::json!({ "name": c.name, "arguments": c.arguments })))
+                .collect();
+            format!("assistant: {}\n{}", m.content, calls)
         }
+        _ => format!("{}: {}\n", m.role, m.content),
     }
 }
 
+// The text-generation endpoint takes a single string, so multi-turn
+// conversations are sent as a plain transcript ending with the assistant cue.
+// Without native structured output the schema is stated in the prompt.
+fn flatten_messages(messages: &[Message], options: &RequestOptions<'_>) -> String {
+    let tools = options.tools;
+    if let ([only], true, None) = (messages, tools.is_empty(), options.response_schema) {
+        return only.content.clone();
+    }
+    let mut transcript = if tools.is_empty() { String::new() } else { tool_instructions(tools) };
+    if let Some(schema) = options.response_schema {
+        transcript.push_str(&format!("system: Reply only with JSON matching this schema: {}\n", schema));
+    }
+    transcript.extend(messages.iter().map(format_message));
+    transcript.push_str("assistant:");
+    transcript
+}
+
 // --- Generate Function ---
-#[instrument(skip(client, config, prompt))]
+#[instrument(skip(client, config, messages, options))]
 pub async fn generate(
     client: &Client,
     config: &Config,
-    prompt: &str,
-) -> Result<String> {
+    messages: &[Message],
+    options: &RequestOptions<'_>,
+) -> Result<Completion> {
     let api_key = config.huggingface_api_key.as_deref().ok_or_else(|| anyhow!("HUGGINGFACE_API_KEY is not set."))?;
     let model_name = &config.default_huggingface_model;
     let url = format!("https://api-inference.huggingface.co/models/{}", model_name);
 
     let request_payload = HuggingFaceRequest {
-        inputs: prompt.to_string(),
+        inputs: flatten_messages(messages, options),
+        parameters: options.temperature.map(|temperature| HuggingFaceParameters { temperature }),
     };
 
     debug!(?url, ?request_payload, "Sending generate request to Hugging Face API");
 
+    let start = Instant::now();
     let response = client
         .post(&url)
         .bearer_auth(api_key)
@@ -84,7 +165,59 @@ pub async fn generate(
         return Err(anyhow!("Hugging Face API Error: {}", err));
     }
 
-    huggingface_response.generated_text.ok_or_else(|| anyhow!("No generated text in Hugging Face response"))
+    let mut text = huggingface_response.generated_text.ok_or_else(|| anyhow!("No generated text in Hugging Face response"))?;
+    let tool_calls: Vec<ToolCall> = if options.tools.is_empty() { Vec::new() } else { parse_tool_call(&text).into_iter().collect() };
+    if !tool_calls.is_empty() {
+        // Keep only the explanation before the block
+        text.truncate(text.find(TOOL_CALL_FENCE).unwrap_or(text.len()));
+        text = text.trim_end().to_string();
+    }
+
+    // The inference API does not report token counts
+    let elapsed = start.elapsed();
+    let usage = Usage {
+        total_duration: elapsed,
+        time_to_first_token: Some(elapsed),
+        ..Usage::default()
+    };
+    Ok(Completion { text, usage, tool_calls })
+}
+
+// --- Embed Function ---
+// The feature-extraction pipeline returns one vector per input for sentence
+// embedding models, or one vector per token for plain encoders; the latter
+// are mean-pooled.
+#[instrument(skip(client, config, inputs))]
+pub async fn embed(client: &Client, config: &Config, model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>> {
+    let api_key = config.huggingface_api_key.as_deref().ok_or_else(|| anyhow!("HUGGINGFACE_API_KEY is not set."))?;
+    let url = format!("https://api-inference.huggingface.co/pipeline/feature-extraction/{}", model);
+
+    let response = client
+        .post(&url)
+        .bearer_auth(api_key)
+        .json(&serde_json::json!({ "inputs": inputs }))
+        .send()
+        .await
+        .context("Failed to send feature-extraction request to Hugging Face API")?;
+    let vectors: Vec<serde_json::Value> = handle_api_response(response, &url, "Hugging Face feature-extraction").await?;
+
+    vectors
+        .iter()
+        .map(|vector| {
+            if let Ok(sentence) = serde_json::from_value::<Vec<f32>>(vector.clone()) {
+                return Ok(sentence);
+            }
+            let tokens: Vec<Vec<f32>> = serde_json::from_value(vector.clone())
+                .context("Unexpected feature-extraction output shape")?;
+            let dims = tokens.first().map_or(0, Vec::len);
+            let mut pooled = vec![0.0; dims];
+            for token in &tokens {
+                pooled.iter_mut().zip(token).for_each(|(sum, v)| *sum += v);
+            }
+            pooled.iter_mut().for_each(|v| *v /= tokens.len().max(1) as f32);
+            Ok(pooled)
+        })
+        .collect()
 }
 
 // --- List Models Function ---
@@ -102,7 +235,7 @@ pub async fn check_connection(client: &Client, config: &Config) -> Result<()> {
     // TODO: Implement a proper connection check for Hugging Face
     // For now, we'll just try to generate something
     debug!("Checking Hugging Face connection status...");
-    generate(client, config, "test").await?;
+    generate(client, config, &[Message::user("test")], &RequestOptions::default()).await?;
     debug!("Hugging Face connection check successful.");
     Ok(())
 }
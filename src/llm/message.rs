@@ -0,0 +1,142 @@
+// src/llm/message.rs
+
+use crate::config::LlmProvider;
+use crate::llm::usage::Usage;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// --- Provider-Agnostic Chat Message ---
+// Each backend maps these onto its own request format.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+    // Result of a tool call requested by the assistant
+    Tool,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+            Role::Tool => "tool",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+// A function call requested by the model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    // Provider-assigned id (generated locally for providers without ids)
+    pub id: String,
+    pub name: String,
+    pub arguments: serde_json::Value,
+}
+
+// An image sent inline with a user message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Image {
+    pub mime_type: String,
+    // Base64-encoded file contents
+    pub data: String,
+}
+
+impl Image {
+    // "data:<mime>;base64,<data>" as used by OpenAI-style APIs
+    pub fn data_uri(&self) -> String {
+        format!("data:{};base64,{}", self.mime_type, self.data)
+    }
+}
+
+// Who produced an answer and what it cost; kept for exports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnswerMeta {
+    pub provider: LlmProvider,
+    pub model: String,
+    // Per-request override (/retry -t, templates); None means the configured default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    pub usage: Usage,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    pub role: Role,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<Image>,
+    // Set on assistant messages that request tool calls
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    // Set on tool messages: the call this is the result of
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+    // When the message was added (missing in sessions saved by older versions)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<DateTime<Local>>,
+    // Set on assistant messages produced in this tool
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<AnswerMeta>,
+    // Set on prompts whose content was expanded (@file contents, retrieved
+    // excerpts, captured command output): the prompt as typed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub typed: Option<String>,
+}
+
+impl Message {
+    fn new(role: Role, content: String) -> Self {
+        Message {
+            role,
+            content,
+            images: Vec::new(),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+            tool_name: None,
+            timestamp: Some(Local::now()),
+            meta: None,
+            typed: None,
+        }
+    }
+
+    pub fn system(content: impl Into<String>) -> Self {
+        Message::new(Role::System, content.into())
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Message::new(Role::User, content.into())
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Message::new(Role::Assistant, content.into())
+    }
+
+    pub fn user_with_images(content: impl Into<String>, images: Vec<Image>) -> Self {
+        Message { images, ..Message::new(Role::User, content.into()) }
+    }
+
+    pub fn assistant_with_tool_calls(content: impl Into<String>, tool_calls: Vec<ToolCall>) -> Self {
+        Message { tool_calls, ..Message::new(Role::Assistant, content.into()) }
+    }
+
+    pub fn tool_result(call: &ToolCall, content: impl Into<String>) -> Self {
+        Message {
+            tool_call_id: Some(call.id.clone()),
+            tool_name: Some(call.name.clone()),
+            ..Message::new(Role::Tool, content.into())
+        }
+    }
+}
@@ -3,4 +3,215 @@ pub mod ollama;
 pub mod gemini;
 pub mod groq;
 pub mod openai_compatible;
-pub mod huggingface;
\ No newline at end of file
+pub mod huggingface;
+pub mod message;
+pub mod usage;
+
+use crate::config::{Config, LlmProvider};
+use crate::error::{is_retryable, Result};
+use anyhow::anyhow;
+use reqwest::Client;
+use crate::tools::ToolSpec;
+use message::{Message, ToolCall};
+use tracing::{info, warn};
+use usage::Usage;
+
+// --- Backend Result ---
+// What every backend's `generate` returns.
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub text: String,
+    pub usage: Usage,
+    // Tool calls the model asked for (empty when it answered directly)
+    pub tool_calls: Vec<ToolCall>,
+}
+
+// --- Request Options ---
+// Optional request features shared by every backend.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestOptions<'a> {
+    // Tools the model may call
+    pub tools: &'a [ToolSpec],
+    // JSON schema the reply must follow (structured output)
+    pub response_schema: Option<&'a serde_json::Value>,
+    // Sampling temperature for this request (e.g. /retry --temperature)
+    pub temperature: Option<f32>,
+}
+
+// --- Generation Result ---
+#[derive(Debug, Clone)]
+pub struct Generation {
+    pub text: String,
+    pub usage: Usage,
+    // The provider that actually produced the answer
+    pub provider: LlmProvider,
+    pub model: String,
+    pub tool_calls: Vec<ToolCall>,
+}
+
+// --- Vision Support ---
+// "provider:model" prefixes known to accept images; `vision_models` in the
+// config extends the list.
+const VISION_MODELS: &[&str] = &[
+    "gemini:gemini-1.5",
+    "gemini:gemini-2",
+    "gemini:gemini-pro-vision",
+    "groq:llava",
+    "groq:llama-3.2-11b-vision",
+    "groq:llama-3.2-90b-vision",
+    "groq:meta-llama/llama-4",
+    "ollama:llava",
+    "ollama:bakllava",
+    "ollama:llama3.2-vision",
+    "ollama:moondream",
+];
+
+// Fails with a clear message when the provider's model cannot take images.
+// Ollama is asked about the model's capabilities when it is not listed.
+async fn check_vision(client: &Client, config: &Config, provider: &LlmProvider) -> Result<()> {
+    let model = config.get_provider_model(provider);
+    let key = format!("{}:{}", provider.get_provider_config_name(), model);
+    let listed = VISION_MODELS
+        .iter()
+        .copied()
+        .chain(config.vision_models.iter().map(String::as_str))
+        .any(|prefix| key.starts_with(prefix));
+    let supported = match provider {
+        _ if listed => true,
+        // An unknown answer is left to the server
+        LlmProvider::Ollama => ollama::supports_vision(client, config).await.ok().flatten().unwrap_or(true),
+        _ => false,
+    };
+    if supported {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Model '{}' ({}) does not support image input. Switch to a vision model, or add \"{}\" to vision_models in the config if it does.",
+            model, provider, key
+        ))
+    }
+}
+
+// --- Provider Dispatch ---
+// Sends the conversation to the given provider using its configured default model.
+pub async fn generate(
+    client: &Client,
+    config: &Config,
+    provider: &LlmProvider,
+    messages: &[Message],
+    options: &RequestOptions<'_>,
+) -> Result<Completion> {
+    if messages.iter().any(|m| !m.images.is_empty()) {
+        check_vision(client, config, provider).await?;
+    }
+    match provider {
+        LlmProvider::Ollama => ollama::generate(client, config, None, messages, options).await,
+        LlmProvider::Gemini => {
+            if config.gemini_api_key.is_none() {
+                Err(anyhow!("GEMINI_API_KEY not set."))
+            } else {
+                gemini::generate(client, config, messages, options).await
+            }
+        }
+        LlmProvider::Groq => {
+            if config.groq_api_key.is_none() {
+                Err(anyhow!("GROQ_API_KEY not set."))
+            } else {
+                groq::generate(client, config, messages, options).await
+            }
+        }
+        LlmProvider::HuggingFace => {
+            if config.huggingface_api_key.is_none() {
+                Err(anyhow!("HUGGINGFACE_API_KEY not set."))
+            } else {
+                huggingface::generate(client, config, messages, options).await
+            }
+        }
+    }
+}
+
+// --- Embeddings Dispatch ---
+// Embeds each input with the provider's embedding model; one vector per input,
+// in order. Callers batch large inputs (see `embedding_batch_size`).
+pub async fn embed(client: &Client, config: &Config, provider: &LlmProvider, inputs: &[String]) -> Result<Vec<Vec<f32>>> {
+    let model = config
+        .get_embedding_model(provider)
+        .ok_or_else(|| anyhow!("No embedding model configured for {}.", provider))?;
+    if *provider != LlmProvider::Ollama && config.get_provider_api_key(provider).is_none() {
+        return Err(anyhow!("{} API key not set.", provider));
+    }
+    let vectors = match provider {
+        LlmProvider::Ollama => ollama::embed(client, config, model, inputs).await?,
+        LlmProvider::Gemini => gemini::embed(client, config, model, inputs).await?,
+        LlmProvider::Groq => groq::embed(client, config, model, inputs).await?,
+        LlmProvider::HuggingFace => huggingface::embed(client, config, model, inputs).await?,
+    };
+    if vectors.len() != inputs.len() {
+        return Err(anyhow!("{} returned {} embeddings for {} inputs", provider, vectors.len(), inputs.len()));
+    }
+    Ok(vectors)
+}
+
+// Context window of the provider's configured default model, if it can be found out
+pub async fn context_length(client: &Client, config: &Config, provider: &LlmProvider) -> Result<Option<u32>> {
+    match provider {
+        LlmProvider::Ollama => ollama::context_length(client, config).await,
+        LlmProvider::Gemini => gemini::context_length(client, config).await,
+        LlmProvider::Groq => groq::context_length(client, config).await,
+        LlmProvider::HuggingFace => Ok(None),
+    }
+}
+
+// --- Fallback Dispatch ---
+// Tries the active provider first, then each configured fallback in order.
+// Only retryable or connection errors move on to the next provider; any other
+// error (bad request, blocked prompt, ...) is returned immediately.
+// Providers in `blocked` (e.g. over budget) are never contacted.
+pub async fn generate_with_fallback(
+    client: &Client,
+    config: &Config,
+    messages: &[Message],
+    options: &RequestOptions<'_>,
+    blocked: &[LlmProvider],
+) -> Result<Generation> {
+    let chain = config.provider_chain();
+    let mut last_error = None;
+
+    for (i, provider) in chain.iter().enumerate() {
+        if blocked.contains(provider) {
+            eprintln!("Skipping {}: monthly hard budget exceeded.", provider);
+            last_error = Some(anyhow!("Monthly hard budget exceeded; refusing to send to {}", provider));
+            continue;
+        }
+        // Fallbacks without credentials are skipped rather than failing the chain
+        if i > 0 && *provider != LlmProvider::Ollama && config.get_provider_api_key(provider).is_none() {
+            info!("Skipping fallback provider {}: no API key configured", provider);
+            continue;
+        }
+        if i > 0 {
+            eprintln!("... falling back to {} ...", provider);
+        }
+
+        match generate(client, config, provider, messages, options).await {
+            Ok(Completion { text, usage, tool_calls }) => {
+                return Ok(Generation {
+                    text,
+                    usage,
+                    provider: provider.clone(),
+                    model: config.get_provider_model(provider).to_string(),
+                    tool_calls,
+                })
+            }
+            Err(e) if is_retryable(&e) => {
+                warn!("Provider {} failed with a retryable error: {:?}", provider, e);
+                if i + 1 < chain.len() {
+                    eprintln!("{} failed: {}", provider, e);
+                }
+                last_error = Some(e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| anyhow!("No provider available to handle the request")))
+}
@@ -1,38 +1,146 @@
 // src/llm/ollama.rs
 
 use crate::config::Config;
-use crate::error::Result;
+use crate::error::{ApiStatusError, Result};
+use crate::llm::message::{Message, ToolCall};
+use crate::llm::usage::Usage;
+use crate::llm::{Completion, RequestOptions};
 use anyhow::{anyhow, Context};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tracing::{debug, error, instrument};
 
 // --- Request Structs ---
 
 #[derive(Serialize, Debug)]
-struct OllamaRequest {
+struct OllamaChatRequest {
     model: String,
-    prompt: String,
+    messages: Vec<OllamaMessage>,
     stream: bool, // We want the full response at once for this simple REPL
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<OllamaTool>,
+    // A JSON schema constrains the output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<serde_json::Value>,
+    // Model parameters such as temperature
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct OllamaMessage {
+    role: String,
+    content: String,
+    // Base64-encoded images for vision models
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    images: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<OllamaToolCall>,
+    // Name of the tool a "tool" message answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tool_name: Option<String>,
+}
+
+impl From<&Message> for OllamaMessage {
+    fn from(m: &Message) -> Self {
+        OllamaMessage {
+            role: m.role.to_string(),
+            content: m.content.clone(),
+            images: m.images.iter().map(|image| image.data.clone()).collect(),
+            tool_calls: m
+                .tool_calls
+                .iter()
+                .map(|call| OllamaToolCall {
+                    function: OllamaFunctionCall { name: call.name.clone(), arguments: call.arguments.clone() },
+                })
+                .collect(),
+            tool_name: m.tool_name.clone(),
+        }
+    }
+}
+
+// --- Tool Calling Structs ---
+// Same shape as OpenAI, except arguments are a JSON object and calls have no ids.
+
+#[derive(Serialize, Debug)]
+struct OllamaTool {
+    #[serde(rename = "type")]
+    tool_type: &'static str, // Always "function"
+    function: OllamaFunction,
+}
+
+#[derive(Serialize, Debug)]
+struct OllamaFunction {
+    name: String,
+    description: String,
+    parameters: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct OllamaToolCall {
+    function: OllamaFunctionCall,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct OllamaFunctionCall {
+    name: String,
+    arguments: serde_json::Value,
+}
+
+#[derive(Serialize, Debug)]
+struct OllamaShowRequest {
+    model: String,
+}
+
+#[derive(Serialize, Debug)]
+struct OllamaEmbedRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
 }
 
 // --- Response Structs ---
 
+#[allow(dead_code)] // Mirrors the API; not every field is read
 #[derive(Deserialize, Debug)]
-struct OllamaResponse {
+struct OllamaChatResponse {
     model: String,
     created_at: String,
-    response: String,
+    message: OllamaMessage,
     done: bool,
-    // Includes timings and context if needed
-    // total_duration: Option<u64>,
-    // prompt_eval_count: Option<u32>,
-    // eval_count: Option<u32>,
+    // Timings are reported in nanoseconds
+    total_duration: Option<u64>,
+    load_duration: Option<u64>,
+    prompt_eval_count: Option<u32>,
+    prompt_eval_duration: Option<u64>,
+    eval_count: Option<u32>,
+    eval_duration: Option<u64>,
+}
+
+impl OllamaChatResponse {
+    fn usage(&self, elapsed: Duration) -> Usage {
+        let total_duration = self.total_duration.map_or(elapsed, Duration::from_nanos);
+        let time_to_first_token = match (self.load_duration, self.prompt_eval_duration) {
+            (None, None) => None,
+            (load, eval) => Some(Duration::from_nanos(load.unwrap_or(0) + eval.unwrap_or(0))),
+        };
+        let usage = Usage {
+            prompt_tokens: self.prompt_eval_count,
+            completion_tokens: self.eval_count,
+            total_duration,
+            time_to_first_token,
+            tokens_per_sec: None,
+        };
+        match self.eval_duration {
+            Some(eval) => usage.with_rate(Duration::from_nanos(eval)),
+            None => usage.with_rate(total_duration),
+        }
+    }
 }
 
 // --- Model Listing Structs ---
 
+#[allow(dead_code)] // Mirrors the API; not every field is read
 #[derive(Deserialize, Debug)]
 struct OllamaTag {
     name: String,
@@ -46,6 +154,25 @@ struct OllamaTagsResponse {
     models: Vec<OllamaTag>,
 }
 
+// --- Embedding Structs ---
+
+#[derive(Deserialize, Debug)]
+struct OllamaEmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+// --- Model Info Structs ---
+
+#[derive(Deserialize, Debug)]
+struct OllamaShowResponse {
+    // Modelfile parameters, one "name value" pair per line (e.g. "num_ctx 8192")
+    parameters: Option<String>,
+    // Architecture-prefixed keys such as "llama.context_length"
+    model_info: Option<serde_json::Map<String, serde_json::Value>>,
+    // e.g. ["completion", "vision", "tools"]; missing on older servers
+    capabilities: Option<Vec<String>>,
+}
+
 // --- Helper function to handle API responses ---
 async fn handle_api_response<T: serde::de::DeserializeOwned + std::fmt::Debug>(
     response: reqwest::Response,
@@ -68,42 +195,138 @@ async fn handle_api_response<T: serde::de::DeserializeOwned + std::fmt::Debug>(
                 response_body = ?body_string,
                 "Failed to parse {} response", operation_name
             );
-            let base_msg = if status.is_success() { format!("Failed to parse successful {} response", operation_name) } else { format!("API {} request failed", operation_name) };
-            Err(anyhow!("{} (Status: {}): {}. Body: {}", base_msg, status, parse_error, body_string))
+            if !status.is_success() {
+                return Err(ApiStatusError { status: status.as_u16(), message: format!("{} request failed: {}", operation_name, body_string) }.into());
+            }
+            Err(anyhow!("Failed to parse successful {} response (Status: {}): {}. Body: {}", operation_name, status, parse_error, body_string))
         }
     }
 }
 
 // --- Generate Function ---
-#[instrument(skip(client, config, prompt))]
+#[instrument(skip(client, config, messages, options))]
 pub async fn generate(
     client: &Client,
     config: &Config,
     model: Option<&str>, // Allow overriding default model
-    prompt: &str,
-) -> Result<String> {
+    messages: &[Message],
+    options: &RequestOptions<'_>,
+) -> Result<Completion> {
     let target_model = model.unwrap_or(&config.default_ollama_model);
-    let url = format!("{}/api/generate", config.ollama_base_url);
+    let url = format!("{}/api/chat", config.ollama_base_url);
 
-    let request_payload = OllamaRequest {
+    let request_payload = OllamaChatRequest {
         model: target_model.to_string(),
-        prompt: prompt.to_string(),
+        messages: messages.iter().map(OllamaMessage::from).collect(),
         stream: false,
+        tools: options
+            .tools
+            .iter()
+            .map(|tool| OllamaTool {
+                tool_type: "function",
+                function: OllamaFunction {
+                    name: tool.name.clone(),
+                    description: tool.description.clone(),
+                    parameters: tool.parameters.clone(),
+                },
+            })
+            .collect(),
+        format: options.response_schema.cloned(),
+        options: options.temperature.map(|temperature| serde_json::json!({ "temperature": temperature })),
     };
 
-    debug!(?request_payload, "Sending generate request to Ollama");
+    debug!(?request_payload, "Sending chat request to Ollama");
 
+    let start = Instant::now();
     let response = client
         .post(&url)
         .json(&request_payload)
         .send()
         .await
-        .context(format!("Failed to send generate request to Ollama at {}", url))?;
+        .context(format!("Failed to send chat request to Ollama at {}", url))?;
 
     // Use the helper function to handle the response
-    let ollama_response: OllamaResponse = handle_api_response(response, &url, "Ollama generate").await?;
+    let ollama_response: OllamaChatResponse = handle_api_response(response, &url, "Ollama chat").await?;
+
+    let usage = ollama_response.usage(start.elapsed());
+    // Ollama does not assign call ids, so number them
+    let tool_calls = ollama_response
+        .message
+        .tool_calls
+        .into_iter()
+        .enumerate()
+        .map(|(i, call)| ToolCall { id: format!("call_{}", i), name: call.function.name, arguments: call.function.arguments })
+        .collect();
+    Ok(Completion { text: ollama_response.message.content, usage, tool_calls })
+}
+
+async fn show_model(client: &Client, config: &Config) -> Result<OllamaShowResponse> {
+    let url = format!("{}/api/show", config.ollama_base_url);
+    let request_payload = OllamaShowRequest { model: config.default_ollama_model.clone() };
+
+    let response = client
+        .post(&url)
+        .json(&request_payload)
+        .send()
+        .await
+        .context(format!("Failed to send show request to Ollama at {}", url))?;
+
+    handle_api_response(response, &url, "Ollama show").await
+}
+
+// --- Embed Function ---
+#[instrument(skip(client, config, inputs))]
+pub async fn embed(client: &Client, config: &Config, model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>> {
+    let url = format!("{}/api/embed", config.ollama_base_url);
+    let request_payload = OllamaEmbedRequest { model, input: inputs };
+
+    let response = client
+        .post(&url)
+        .json(&request_payload)
+        .send()
+        .await
+        .context(format!("Failed to send embed request to Ollama at {}", url))?;
+
+    let embed_response: OllamaEmbedResponse = handle_api_response(response, &url, "Ollama embed").await?;
+    Ok(embed_response.embeddings)
+}
+
+// --- Vision Support Function ---
+// Whether the model reports the "vision" capability; `None` if the server
+// does not report capabilities.
+#[instrument(skip(client, config))]
+pub async fn supports_vision(client: &Client, config: &Config) -> Result<Option<bool>> {
+    let show_response = show_model(client, config).await?;
+    Ok(show_response.capabilities.map(|caps| caps.iter().any(|c| c == "vision")))
+}
+
+// --- Context Length Function ---
+// Reads the model's context window from /api/show. A `num_ctx` parameter in
+// the Modelfile limits the runtime window, so the smaller of the two wins.
+#[instrument(skip(client, config))]
+pub async fn context_length(client: &Client, config: &Config) -> Result<Option<u32>> {
+    let show_response = show_model(client, config).await?;
+
+    let trained = show_response.model_info.as_ref().and_then(|info| {
+        info.iter()
+            .find(|(key, _)| key.ends_with(".context_length"))
+            .and_then(|(_, value)| value.as_u64())
+            .map(|v| v as u32)
+    });
+    let num_ctx = show_response.parameters.as_deref().and_then(|params| {
+        params.lines().find_map(|line| {
+            let mut parts = line.split_whitespace();
+            match (parts.next(), parts.next()) {
+                (Some("num_ctx"), Some(value)) => value.parse::<u32>().ok(),
+                _ => None,
+            }
+        })
+    });
 
-    Ok(ollama_response.response)
+    Ok(match (trained, num_ctx) {
+        (Some(t), Some(n)) => Some(t.min(n)),
+        (t, n) => t.or(n),
+    })
 }
 
 // --- List Models Function ---
@@ -1,11 +1,14 @@
 // src/llm/openai_compatible.rs
 
-use crate::error::Result;
+use crate::error::{ApiStatusError, Result};
+use crate::llm::message::{Message, Role, ToolCall};
+use crate::llm::usage::Usage;
+use crate::llm::{Completion, RequestOptions};
 use anyhow::{anyhow, Context};
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tracing::{debug, error, instrument};
 
 // --- Common Request Structures ---
@@ -14,21 +17,115 @@ use tracing::{debug, error, instrument};
 pub struct ChatCompletionRequest {
     pub model: String,
     pub messages: Vec<ChatMessage>,
-    // Add other common OpenAI params if needed (temperature, max_tokens, stream, etc.)
-    // #[serde(skip_serializing_if = "Option::is_none")]
-    // pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ToolDefinition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    // Add other common OpenAI params if needed (max_tokens, stream, etc.)
     // #[serde(skip_serializing_if = "Option::is_none")]
     // pub stream: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)] // Clone needed for potential retries or logging
 pub struct ChatMessage {
-    pub role: String, // "system", "user", "assistant"
-    pub content: String,
+    pub role: String, // "system", "user", "assistant", "tool"
+    // Null on assistant messages that only carry tool calls
+    pub content: Option<ChatContent>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ChatToolCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+// Plain text, or text plus images as content parts
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ChatContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrl },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImageUrl {
+    // A data URI for inline images
+    pub url: String,
+}
+
+// --- Tool Calling Structures ---
+
+#[derive(Serialize, Debug)]
+pub struct ToolDefinition {
+    #[serde(rename = "type")]
+    pub tool_type: &'static str, // Always "function"
+    pub function: FunctionDefinition,
+}
+
+#[derive(Serialize, Debug)]
+pub struct FunctionDefinition {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChatToolCall {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub call_type: String, // "function"
+    pub function: FunctionCall,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FunctionCall {
+    pub name: String,
+    // JSON-encoded arguments, as a string
+    pub arguments: String,
+}
+
+impl From<&Message> for ChatMessage {
+    fn from(m: &Message) -> Self {
+        let tool_calls: Vec<ChatToolCall> = m
+            .tool_calls
+            .iter()
+            .map(|call| ChatToolCall {
+                id: call.id.clone(),
+                call_type: "function".to_string(),
+                function: FunctionCall { name: call.name.clone(), arguments: call.arguments.to_string() },
+            })
+            .collect();
+        // Assistant tool-call messages may have no text
+        let content = if m.role == Role::Assistant && m.content.is_empty() && !tool_calls.is_empty() {
+            None
+        } else if m.images.is_empty() {
+            Some(ChatContent::Text(m.content.clone()))
+        } else {
+            let mut parts = vec![ContentPart::Text { text: m.content.clone() }];
+            parts.extend(m.images.iter().map(|image| ContentPart::ImageUrl { image_url: ImageUrl { url: image.data_uri() } }));
+            Some(ChatContent::Parts(parts))
+        };
+        ChatMessage { role: m.role.to_string(), content, tool_calls, tool_call_id: m.tool_call_id.clone() }
+    }
+}
+
+// Arguments arrive as a JSON string; keep the raw string if it is not valid JSON
+fn parse_tool_call(call: &ChatToolCall) -> ToolCall {
+    let arguments = serde_json::from_str(&call.function.arguments)
+        .unwrap_or_else(|_| serde_json::Value::String(call.function.arguments.clone()));
+    ToolCall { id: call.id.clone(), name: call.function.name.clone(), arguments }
 }
 
 // --- Common Response Structures ---
 
+#[allow(dead_code)] // Mirrors the API; not every field is read
 #[derive(Deserialize, Debug)]
 pub struct ChatCompletionResponse {
     pub id: Option<String>,
@@ -36,11 +133,12 @@ pub struct ChatCompletionResponse {
     pub created: Option<u64>,
     pub model: Option<String>,
     pub choices: Vec<ChatChoice>,
-    // pub usage: Option<CompletionUsage>, // Add if needed
+    pub usage: Option<CompletionUsage>,
     // Error structure can vary, sometimes it's top-level
     pub error: Option<ApiError>,
 }
 
+#[allow(dead_code)] // Mirrors the API; not every field is read
 #[derive(Deserialize, Debug)]
 pub struct ChatChoice {
     pub index: u32,
@@ -49,13 +147,25 @@ pub struct ChatChoice {
     pub finish_reason: Option<String>, // e.g., "stop", "length"
 }
 
+#[allow(dead_code)] // Mirrors the API; not every field is read
 #[derive(Deserialize, Debug)]
 pub struct ResponseMessage {
     pub role: String, // "assistant"
     pub content: Option<String>, // Content can sometimes be null
+    #[serde(default)]
+    pub tool_calls: Vec<ChatToolCall>,
+}
+
+#[allow(dead_code)] // Mirrors the API; not every field is read
+#[derive(Deserialize, Debug)]
+pub struct CompletionUsage {
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    pub total_tokens: Option<u32>,
 }
 
 // Reusable error structure (matching previous definition)
+#[allow(dead_code)] // Mirrors the API; not every field is read
 #[derive(Deserialize, Debug)]
 pub struct ApiError {
     pub message: String,
@@ -65,8 +175,29 @@ pub struct ApiError {
     pub code: Option<String>, // Often a string code like "invalid_api_key"
 }
 
+// --- Embedding Structures ---
+
+#[derive(Serialize, Debug)]
+pub struct EmbeddingRequest<'a> {
+    pub model: &'a str,
+    pub input: &'a [String],
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EmbeddingResponse {
+    pub data: Vec<EmbeddingData>,
+    pub error: Option<ApiError>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EmbeddingData {
+    pub index: usize,
+    pub embedding: Vec<f32>,
+}
+
 // --- Model Listing Structures ---
 
+#[allow(dead_code)] // Mirrors the API; not every field is read
 #[derive(Deserialize, Debug)]
 pub struct ListModelsResponse {
     pub object: String, // Typically "list"
@@ -75,6 +206,7 @@ pub struct ListModelsResponse {
     pub error: Option<ApiError>,
 }
 
+#[allow(dead_code)] // Mirrors the API; not every field is read
 #[derive(Deserialize, Debug)]
 pub struct ModelInfo {
     pub id: String, // The model ID/name
@@ -82,6 +214,8 @@ pub struct ModelInfo {
     pub created: Option<u64>,
     #[serde(rename = "owned_by")]
     pub owned_by: Option<String>,
+    // Groq extension; not part of the OpenAI schema
+    pub context_window: Option<u32>,
 }
 
 
@@ -118,35 +252,55 @@ async fn handle_api_response<T: serde::de::DeserializeOwned + std::fmt::Debug>(
                 response_body = ?body_string,
                 "Failed to parse {} response", operation_name
             );
-            let base_msg = if status.is_success() { format!("Failed to parse successful {} response", operation_name) } else { format!("API {} request failed", operation_name) };
-            Err(anyhow!("{} (Status: {}): {}. Body: {}", base_msg, status, parse_error, body_string))
+            if !status.is_success() {
+                return Err(ApiStatusError { status: status.as_u16(), message: format!("{} request failed: {}", operation_name, body_string) }.into());
+            }
+            Err(anyhow!("Failed to parse successful {} response (Status: {}): {}. Body: {}", operation_name, status, parse_error, body_string))
         }
     }
 }
 
-#[instrument(skip(client, api_key, base_url, prompt))]
+#[instrument(skip(client, api_key, base_url, messages, options))]
 pub async fn generate(
     client: &Client,
     api_key: &str,
     base_url: &str,
     model: &str,
-    prompt: &str,
-    // TODO: Pass temperature, max_tokens etc. if needed by provider
-) -> Result<String> {
+    messages: &[Message],
+    options: &RequestOptions<'_>,
+    // TODO: Pass max_tokens etc. if needed by provider
+) -> Result<Completion> {
     let url = format!("{}/chat/completions", base_url.trim_end_matches('/'));
     let headers = build_headers(api_key)?;
 
     let request_payload = ChatCompletionRequest {
         model: model.to_string(),
-        messages: vec![ChatMessage {
-            role: "user".to_string(),
-            content: prompt.to_string(),
-        }],
+        messages: messages.iter().map(ChatMessage::from).collect(),
+        tools: options
+            .tools
+            .iter()
+            .map(|tool| ToolDefinition {
+                tool_type: "function",
+                function: FunctionDefinition {
+                    name: tool.name.clone(),
+                    description: tool.description.clone(),
+                    parameters: tool.parameters.clone(),
+                },
+            })
+            .collect(),
+        response_format: options.response_schema.map(|schema| {
+            serde_json::json!({
+                "type": "json_schema",
+                "json_schema": { "name": "response", "schema": schema }
+            })
+        }),
+        temperature: options.temperature,
         // stream: Some(false), // Explicitly non-streaming if needed
     };
 
     debug!(?url, model, "Sending chat completion request"); // Don't log full payload by default
 
+    let start = Instant::now();
     let response = client
         .post(&url)
         .headers(headers)
@@ -164,15 +318,61 @@ pub async fn generate(
         return Err(anyhow!("API Error: {} (Type: {:?}, Code: {:?})", api_error.message, api_error.error_type, api_error.code));
     }
 
-    // Extract text content
-    let text_content = parsed_response.choices
+    // Extract text content and any requested tool calls
+    let message = &parsed_response.choices
         .first()
-        .and_then(|choice| choice.message.content.as_deref())
-        .ok_or_else(|| anyhow!("Failed to extract text content from response choices"))?;
+        .ok_or_else(|| anyhow!("Response contained no choices"))?
+        .message;
+    let tool_calls: Vec<ToolCall> = message.tool_calls.iter().map(parse_tool_call).collect();
+    let text_content = match (message.content.as_deref(), tool_calls.is_empty()) {
+        (Some(text), _) => text,
+        (None, false) => "",
+        (None, true) => return Err(anyhow!("Failed to extract text content from response choices")),
+    };
 
     // Consider checking finish_reason if needed
 
-    Ok(text_content.to_string())
+    let elapsed = start.elapsed();
+    let usage = Usage {
+        prompt_tokens: parsed_response.usage.as_ref().and_then(|u| u.prompt_tokens),
+        completion_tokens: parsed_response.usage.as_ref().and_then(|u| u.completion_tokens),
+        total_duration: elapsed,
+        time_to_first_token: Some(elapsed),
+        tokens_per_sec: None,
+    }
+    .with_rate(elapsed);
+
+    Ok(Completion { text: text_content.to_string(), usage, tool_calls })
+}
+
+#[instrument(skip(client, api_key, base_url, inputs))]
+pub async fn embed(
+    client: &Client,
+    api_key: &str,
+    base_url: &str,
+    model: &str,
+    inputs: &[String],
+) -> Result<Vec<Vec<f32>>> {
+    let url = format!("{}/embeddings", base_url.trim_end_matches('/'));
+    let headers = build_headers(api_key)?;
+
+    debug!(?url, model, count = inputs.len(), "Sending embeddings request");
+    let response = client
+        .post(&url)
+        .headers(headers)
+        .json(&EmbeddingRequest { model, input: inputs })
+        .send()
+        .await
+        .context(format!("Failed to send request to {}", url))?;
+
+    let mut parsed_response: EmbeddingResponse = handle_api_response(response, &url, "embeddings").await?;
+    if let Some(api_error) = parsed_response.error {
+        error!(?api_error, "API returned an error in the response body");
+        return Err(anyhow!("API Error: {} (Type: {:?}, Code: {:?})", api_error.message, api_error.error_type, api_error.code));
+    }
+    // Entries carry their input index; don't rely on response order
+    parsed_response.data.sort_by_key(|d| d.index);
+    Ok(parsed_response.data.into_iter().map(|d| d.embedding).collect())
 }
 
 #[instrument(skip(client, api_key, base_url))]
@@ -181,6 +381,27 @@ pub async fn list_models(
     api_key: &str,
     base_url: &str,
 ) -> Result<Vec<String>> {
+    let model_ids = fetch_models(client, api_key, base_url).await?.into_iter().map(|m| m.id).collect();
+    Ok(model_ids)
+}
+
+// Context window as reported by the /models listing (when the provider includes it)
+#[instrument(skip(client, api_key, base_url))]
+pub async fn context_length(
+    client: &Client,
+    api_key: &str,
+    base_url: &str,
+    model: &str,
+) -> Result<Option<u32>> {
+    let models = fetch_models(client, api_key, base_url).await?;
+    Ok(models.into_iter().find(|m| m.id == model).and_then(|m| m.context_window))
+}
+
+async fn fetch_models(
+    client: &Client,
+    api_key: &str,
+    base_url: &str,
+) -> Result<Vec<ModelInfo>> {
     let url = format!("{}/models", base_url.trim_end_matches('/'));
     let headers = build_headers(api_key)?;
 
@@ -202,8 +423,7 @@ pub async fn list_models(
         return Err(anyhow!("API Error listing models: {}", api_error.message));
     }
 
-    let model_ids = list_response.data.into_iter().map(|m| m.id).collect();
-    Ok(model_ids)
+    Ok(list_response.data)
 }
 
 
@@ -0,0 +1,79 @@
+// src/llm/usage.rs
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+// --- Per-Request Usage ---
+// Token counts are `None` when the provider does not report them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    pub total_duration: Duration,
+    // Without streaming the whole answer arrives at once, so for most providers
+    // this equals the request latency; Ollama reports load + prompt eval time.
+    pub time_to_first_token: Option<Duration>,
+    pub tokens_per_sec: Option<f64>,
+}
+
+impl Usage {
+    // Fills in tokens/sec from the completion count over the given generation time
+    pub fn with_rate(mut self, generation_time: Duration) -> Self {
+        if self.tokens_per_sec.is_none() {
+            if let Some(tokens) = self.completion_tokens {
+                let secs = generation_time.as_secs_f64();
+                if secs > 0.0 {
+                    self.tokens_per_sec = Some(tokens as f64 / secs);
+                }
+            }
+        }
+        self
+    }
+}
+
+fn fmt_tokens(tokens: Option<u32>) -> String {
+    tokens.map_or_else(|| "?".to_string(), |t| t.to_string())
+}
+
+// Single status line printed after each answer
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "tokens: {} in / {} out | {:.2}s",
+            fmt_tokens(self.prompt_tokens),
+            fmt_tokens(self.completion_tokens),
+            self.total_duration.as_secs_f64()
+        )?;
+        if let Some(ttft) = self.time_to_first_token {
+            write!(f, " | ttft {:.2}s", ttft.as_secs_f64())?;
+        }
+        if let Some(rate) = self.tokens_per_sec {
+            write!(f, " | {:.1} tok/s", rate)?;
+        }
+        Ok(())
+    }
+}
+
+// --- Session Totals ---
+#[derive(Debug, Clone, Default)]
+pub struct UsageTotals {
+    pub requests: u32,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_duration: Duration,
+}
+
+impl UsageTotals {
+    pub fn add(&mut self, usage: &Usage) {
+        self.requests += 1;
+        self.prompt_tokens += u64::from(usage.prompt_tokens.unwrap_or(0));
+        self.completion_tokens += u64::from(usage.completion_tokens.unwrap_or(0));
+        self.total_duration += usage.total_duration;
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
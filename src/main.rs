@@ -1,17 +1,68 @@
 // src/main.rs
 
+mod agent;
+mod attachments;
 mod cli;
+mod codeblocks;
 mod config;
+mod context;
+mod conversation;
+mod cost;
+mod embed;
 mod error;
+mod history;
+mod export;
+mod import;
 mod llm;
+mod mcp;
+mod rag;
+mod shell;
+mod structured;
+mod summary;
+mod templates;
+mod tokenizer;
+mod tools;
 
 use anyhow::Context;
+use clap::Parser;
 use reqwest::Client;
+use std::io::{IsTerminal, Read};
+use std::path::PathBuf;
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 use tracing::{info, error};
 
+// --- Command-Line Arguments ---
+// Without a prompt (and with an interactive stdin) the REPL starts.
+#[derive(Parser, Debug)]
+#[command(version, about = "Chat with local and hosted LLMs from the terminal", args_conflicts_with_subcommands = true)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Require the answer to be JSON matching this schema (validated locally)
+    #[arg(long, value_name = "FILE")]
+    json_schema: Option<PathBuf>,
+
+    /// Index this directory and answer every REPL prompt from it
+    #[arg(long, value_name = "DIR")]
+    rag: Option<PathBuf>,
+
+    /// Prompt to send non-interactively; read from stdin when piped
+    prompt: Vec<String>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Create embeddings for text lines or files
+    Embed(embed::EmbedArgs),
+    /// List recent prompts and answers from the history database
+    Log(history::LogArgs),
+}
+
 #[tokio::main]
 async fn main() -> error::Result<()> {
+    let args = Args::parse();
+
     // --- Load .env file ---
     // Place this early, before loading config which reads env vars
     dotenvy::dotenv().ok(); // Ignore error if .env is not found
@@ -19,7 +70,7 @@ async fn main() -> error::Result<()> {
 
     // Initialize tracing
     tracing_subscriber::registry()
-        .with(fmt::layer())
+        .with(fmt::layer().with_writer(std::io::stderr))
         .with(EnvFilter::from_default_env())
         .init();
 
@@ -31,8 +82,48 @@ async fn main() -> error::Result<()> {
     // Create reqwest client
     let client = Client::new();
 
+    if let Some(Command::Log(log_args)) = &args.command {
+        if let Err(e) = history::run_log(log_args) {
+            error!("Listing history failed: {:#}", e);
+            eprintln!("Error: {:#}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Embed(embed_args)) = &args.command {
+        match embed::run_embed(&config, &client, embed_args, true).await {
+            Ok((count, dims)) => eprintln!("Embedded {} input(s) ({} dimensions).", count, dims),
+            Err(e) => {
+                error!("Embedding failed: {:#}", e);
+                eprintln!("Error: {:#}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    // One-shot mode: prompt from the arguments or piped stdin
+    let mut prompt = args.prompt.join(" ");
+    if prompt.is_empty() && !std::io::stdin().is_terminal() {
+        std::io::stdin().read_to_string(&mut prompt).context("Failed to read prompt from stdin")?;
+    }
+    if !prompt.trim().is_empty() || args.json_schema.is_some() {
+        let schema = args.json_schema.as_deref().map(structured::ResponseSchema::load).transpose()?;
+        if prompt.trim().is_empty() {
+            eprintln!("--json-schema needs a prompt (as arguments or on stdin).");
+            std::process::exit(2);
+        }
+        if let Err(e) = cli::oneshot::run_prompt(&config, &client, prompt.trim(), schema.as_ref()).await {
+            error!("One-shot prompt failed: {:#}", e);
+            eprintln!("Error: {:#}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     // Start the interactive REPL mode
-    if let Err(e) = cli::repl::run_interactive(&mut config, &client).await {
+    if let Err(e) = cli::repl::run_interactive(&mut config, &client, args.rag.as_deref()).await {
         error!("Application error: {:?}", e);
         std::process::exit(1);
     }
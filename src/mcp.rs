@@ -0,0 +1,347 @@
+// src/mcp.rs
+use crate::error::Result;
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+// --- Constants ---
+const PROTOCOL_VERSION: &str = "2024-11-05";
+// How long a server may take to answer one request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+// --- Server Configuration ---
+// One entry of `mcp_servers` in config.json, e.g.
+// "stub": { "command": "cargo", "args": ["run", "-q", "--example", "mcp_stub"] }
+// Tool calls need the user's approval unless the server is marked "trusted".
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct McpServerConfig {
+    pub command: String,
+    pub args: Vec<String>,
+    // Extra environment variables for the server process
+    pub env: BTreeMap<String, String>,
+    // Run this server's tools without asking for each call
+    pub trusted: bool,
+}
+
+// --- Discovered Capabilities ---
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct McpTool {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(rename = "inputSchema", default)]
+    pub input_schema: Value,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct McpResource {
+    pub uri: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct McpPrompt {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+// --- JSON-RPC over stdio ---
+// Messages are newline-delimited JSON objects on the child's stdin/stdout.
+
+#[allow(dead_code)] // Mirrors the protocol; not every field is read
+#[derive(Debug, Deserialize)]
+struct RpcMessage {
+    id: Option<Value>,
+    method: Option<String>,
+    result: Option<Value>,
+    error: Option<RpcError>,
+}
+
+#[allow(dead_code)] // Mirrors the protocol; not every field is read
+#[derive(Debug, Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Debug)]
+struct Connection {
+    // Kept so the process is killed when the connection is dropped
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+    next_id: u64,
+}
+
+impl Connection {
+    async fn send(&mut self, message: &Value) -> Result<()> {
+        let mut line = serde_json::to_string(message)?;
+        line.push('\n');
+        self.stdin.write_all(line.as_bytes()).await.context("Failed to write to MCP server")?;
+        self.stdin.flush().await.context("Failed to write to MCP server")
+    }
+
+    async fn notify(&mut self, method: &str) -> Result<()> {
+        self.send(&json!({ "jsonrpc": "2.0", "method": method })).await
+    }
+
+    async fn request(&mut self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })).await?;
+        tokio::time::timeout(REQUEST_TIMEOUT, self.read_response(id))
+            .await
+            .map_err(|_| anyhow!("MCP request '{}' timed out", method))?
+    }
+
+    // Reads until the response with the given id, skipping notifications
+    // and answering server-initiated requests with "method not found"
+    async fn read_response(&mut self, id: u64) -> Result<Value> {
+        loop {
+            let line = self
+                .stdout
+                .next_line()
+                .await
+                .context("Failed to read from MCP server")?
+                .ok_or_else(|| anyhow!("MCP server closed its output"))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let message: RpcMessage = match serde_json::from_str(&line) {
+                Ok(message) => message,
+                Err(e) => {
+                    debug!("Ignoring unparseable MCP line ({}): {}", e, line);
+                    continue;
+                }
+            };
+            match (&message.id, &message.method) {
+                (Some(request_id), Some(method)) => {
+                    debug!("Declining server request '{}'", method);
+                    let reply = json!({
+                        "jsonrpc": "2.0",
+                        "id": request_id,
+                        "error": { "code": -32601, "message": "Method not found" }
+                    });
+                    self.send(&reply).await?;
+                }
+                (Some(response_id), None) if response_id.as_u64() == Some(id) => {
+                    if let Some(error) = message.error {
+                        return Err(anyhow!("MCP error {}: {}", error.code, error.message));
+                    }
+                    return Ok(message.result.unwrap_or(Value::Null));
+                }
+                _ => debug!("Ignoring MCP message: {}", line),
+            }
+        }
+    }
+
+    // Collects a paginated list (tools/list, resources/list, prompts/list)
+    async fn list<T: serde::de::DeserializeOwned>(&mut self, method: &str, key: &str) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = self.request(method, params).await?;
+            let page: Vec<T> = serde_json::from_value(result.get(key).cloned().unwrap_or(json!([])))
+                .context(format!("Malformed {} response", method))?;
+            items.extend(page);
+            cursor = result.get("nextCursor").and_then(Value::as_str).map(str::to_string);
+            if cursor.is_none() {
+                return Ok(items);
+            }
+        }
+    }
+}
+
+// --- Server Handle ---
+#[derive(Debug, Clone)]
+pub struct McpServer {
+    pub name: String,
+    pub tools: Vec<McpTool>,
+    pub resources: Vec<McpResource>,
+    pub prompts: Vec<McpPrompt>,
+    pub trusted: bool,
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl McpServer {
+    // Launches the server, performs the initialize handshake and discovers
+    // whatever the server advertises.
+    pub async fn start(name: &str, config: &McpServerConfig) -> Result<Self> {
+        let mut child = Command::new(&config.command)
+            .args(&config.args)
+            .envs(&config.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            // Server logs would garble the REPL
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .context(format!("Failed to start MCP server '{}' ({})", name, config.command))?;
+        let stdin = child.stdin.take().ok_or_else(|| anyhow!("MCP server stdin unavailable"))?;
+        let stdout = child.stdout.take().ok_or_else(|| anyhow!("MCP server stdout unavailable"))?;
+        let mut connection = Connection { _child: child, stdin, stdout: BufReader::new(stdout).lines(), next_id: 1 };
+
+        let init = connection
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") }
+                }),
+            )
+            .await
+            .context(format!("MCP server '{}' failed to initialize", name))?;
+        connection.notify("notifications/initialized").await?;
+
+        let capabilities = init.get("capabilities").cloned().unwrap_or(Value::Null);
+        let supports = |capability: &str| capabilities.get(capability).is_some();
+        let tools = if supports("tools") { connection.list("tools/list", "tools").await? } else { Vec::new() };
+        let resources = if supports("resources") {
+            connection.list("resources/list", "resources").await.unwrap_or_else(|e| {
+                warn!("MCP server '{}' failed to list resources: {:#}", name, e);
+                Vec::new()
+            })
+        } else {
+            Vec::new()
+        };
+        let prompts = if supports("prompts") {
+            connection.list("prompts/list", "prompts").await.unwrap_or_else(|e| {
+                warn!("MCP server '{}' failed to list prompts: {:#}", name, e);
+                Vec::new()
+            })
+        } else {
+            Vec::new()
+        };
+        info!(
+            "MCP server '{}' ready: {} tools, {} resources, {} prompts",
+            name, tools.len(), resources.len(), prompts.len()
+        );
+
+        Ok(McpServer {
+            name: name.to_string(),
+            tools,
+            resources,
+            prompts,
+            trusted: config.trusted,
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    // Calls a tool and flattens its content to text
+    pub async fn call_tool(&self, tool: &str, arguments: &Value) -> Result<String> {
+        let arguments = if arguments.is_object() { arguments.clone() } else { json!({}) };
+        let result = self
+            .connection
+            .lock()
+            .await
+            .request("tools/call", json!({ "name": tool, "arguments": arguments }))
+            .await?;
+
+        let text = result
+            .get("content")
+            .and_then(Value::as_array)
+            .map(|items| {
+                items
+                    .iter()
+                    .map(|item| match item.get("type").and_then(Value::as_str) {
+                        Some("text") => item.get("text").and_then(Value::as_str).unwrap_or_default().to_string(),
+                        Some("resource") => item.pointer("/resource/text").and_then(Value::as_str).unwrap_or("[resource]").to_string(),
+                        Some(other) => format!("[{} content]", other),
+                        None => item.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+            .unwrap_or_default();
+        if result.get("isError").and_then(Value::as_bool).unwrap_or(false) {
+            return Err(anyhow!("{}", text));
+        }
+        Ok(text)
+    }
+}
+
+// Starts every configured server; failures are reported and skipped
+pub async fn start_servers(servers: &BTreeMap<String, McpServerConfig>) -> Vec<McpServer> {
+    let mut started = Vec::new();
+    for (name, server_config) in servers {
+        match McpServer::start(name, server_config).await {
+            Ok(server) => started.push(server),
+            Err(e) => {
+                warn!("MCP server '{}' unavailable: {:?}", name, e);
+                eprintln!("MCP server '{}' unavailable: {:#}", name, e);
+            }
+        }
+    }
+    started
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::llm::message::ToolCall;
+    use crate::tools::ToolRegistry;
+
+    // examples/mcp_stub.rs, started the way config.json would start it
+    fn stub_config() -> McpServerConfig {
+        McpServerConfig {
+            command: env!("CARGO").to_string(),
+            args: ["run", "-q", "--manifest-path", concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"), "--example", "mcp_stub"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            trusted: true,
+            ..McpServerConfig::default()
+        }
+    }
+
+    fn call(name: &str, arguments: Value) -> ToolCall {
+        ToolCall { id: "call-1".to_string(), name: name.to_string(), arguments }
+    }
+
+    #[tokio::test]
+    async fn stub_tools_run_through_the_registry() {
+        let server = McpServer::start("stub", &stub_config()).await.unwrap();
+        // The stub lists one tool per page
+        let tools: Vec<&str> = server.tools.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(tools, ["echo", "add"]);
+        assert_eq!(server.resources.len(), 1);
+        assert_eq!(server.prompts.len(), 1);
+
+        let mut registry = ToolRegistry::default();
+        registry.register_mcp(&server);
+        let names: Vec<String> = registry.specs().into_iter().map(|spec| spec.name).collect();
+        assert_eq!(names, ["stub__echo", "stub__add"]);
+
+        // Each call is preceded by a log notification the client has to skip
+        let config = Config::default();
+        let echoed = registry.execute(&config, &call("stub__echo", json!({ "text": "hello, tools" }))).await;
+        assert_eq!(echoed, "hello, tools");
+        let sum = registry.execute(&config, &call("stub__add", json!({ "a": 2, "b": 3.5 }))).await;
+        assert_eq!(sum, "5.5");
+
+        let unregistered = registry.execute(&config, &call("stub__missing", json!({}))).await;
+        assert_eq!(unregistered, "Error: unknown tool 'stub__missing'.");
+        // A tool the server rejects comes back as an error carrying its text
+        let error = server.call_tool("missing", &json!({})).await.unwrap_err();
+        assert_eq!(error.to_string(), "unknown tool Some(\"missing\")");
+    }
+}
@@ -0,0 +1,286 @@
+// src/rag.rs
+use crate::attachments;
+use crate::config::{self, Config, LlmProvider};
+use crate::error::Result;
+use crate::llm;
+use anyhow::{anyhow, Context};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tracing::{debug, warn};
+
+// --- Constants ---
+const INDEX_DIR: &str = "index";
+// Directories never worth indexing
+const SKIPPED_DIRS: [&str; 5] = ["target", "node_modules", "__pycache__", "dist", "build"];
+
+// --- On-Disk Index ---
+// One JSON file per indexed directory under <config dir>/index/.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Chunk {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+    pub vector: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedFile {
+    // Modification time (ms since the epoch) when the file was embedded
+    mtime: u64,
+    chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Index {
+    pub root: PathBuf,
+    pub provider: LlmProvider,
+    pub model: String,
+    // Keyed by path relative to `root`
+    files: BTreeMap<String, IndexedFile>,
+}
+
+// What an (incremental) indexing run did
+#[derive(Debug, Default)]
+pub struct IndexStats {
+    pub embedded_files: usize,
+    pub unchanged_files: usize,
+    pub removed_files: usize,
+    pub chunks: usize,
+}
+
+// A retrieved chunk with its similarity to the question
+pub struct Hit<'a> {
+    pub path: &'a str,
+    pub chunk: &'a Chunk,
+    pub score: f32,
+}
+
+// <readable name>-<hash of the canonical path>.json; the hash keeps roots
+// that flatten to the same name (/a/b-c, /a/b_c) apart
+fn index_path(root: &Path) -> PathBuf {
+    let path = root.to_string_lossy();
+    let name: String = path.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    // FNV-1a, stable across builds unlike std's hasher
+    let hash = path.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |h, b| (h ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3));
+    config::config_dir().join(INDEX_DIR).join(format!("{}-{:016x}.json", name.trim_matches('_'), hash))
+}
+
+fn mtime_millis(path: &Path) -> u64 {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+// --- File Discovery ---
+// Text files under `root`, skipping hidden entries, symlinks and build directories.
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .context(format!("Failed to read directory {:?}", dir))?
+        .filter_map(|entry| entry.ok())
+        .collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+        // Symlinks are skipped: they can loop or lead outside the root
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            if !SKIPPED_DIRS.contains(&name.as_str()) {
+                collect_files(&entry.path(), files)?;
+            }
+        } else if file_type.is_file() {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+// (start line, end line, text), 1-based and inclusive
+type LineWindow = (usize, usize, String);
+
+// Splits text into overlapping windows of lines
+fn chunk_lines(text: &str, size: usize, overlap: usize) -> Vec<LineWindow> {
+    let lines: Vec<&str> = text.lines().collect();
+    let size = size.max(1);
+    let step = size.saturating_sub(overlap).max(1);
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        let end = (start + size).min(lines.len());
+        let body = lines[start..end].join("\n");
+        if !body.trim().is_empty() {
+            chunks.push((start + 1, end, body));
+        }
+        if end == lines.len() {
+            break;
+        }
+        start += step;
+    }
+    chunks
+}
+
+// --- Indexing ---
+impl Index {
+    pub fn load(root: &Path) -> Option<Index> {
+        let contents = fs::read_to_string(index_path(root)).ok()?;
+        match serde_json::from_str::<Index>(&contents) {
+            Ok(index) if index.root == root => Some(index),
+            Ok(index) => {
+                warn!("Ignoring index built for {:?} when loading {:?}", index.root, root);
+                None
+            }
+            Err(e) => {
+                warn!("Ignoring unreadable index for {:?}: {}", root, e);
+                None
+            }
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = index_path(&self.root);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context(format!("Failed to create {:?}", parent))?;
+        }
+        fs::write(&path, serde_json::to_string(self)?).context(format!("Failed to write index {:?}", path))
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.files.values().map(|f| f.chunks.len()).sum()
+    }
+
+    pub fn file_count(&self) -> usize {
+        self.files.len()
+    }
+
+    // Builds or refreshes the index for `dir`. Files whose mtime is unchanged
+    // keep their vectors; changing the embedding model re-embeds everything.
+    pub async fn build(client: &Client, config: &Config, dir: &Path) -> Result<(Index, IndexStats)> {
+        let root = dir.canonicalize().context(format!("Cannot index {:?}", dir))?;
+        if !root.is_dir() {
+            return Err(anyhow!("{:?} is not a directory", dir));
+        }
+        let provider = config.embedding_provider().clone();
+        let model = config
+            .get_embedding_model(&provider)
+            .ok_or_else(|| anyhow!("No embedding model configured for {}.", provider))?
+            .to_string();
+
+        let mut previous = Index::load(&root)
+            .filter(|index| index.provider == provider && index.model == model)
+            .map(|index| index.files)
+            .unwrap_or_default();
+        let mut index = Index { root: root.clone(), provider, model, files: BTreeMap::new() };
+        let mut stats = IndexStats::default();
+
+        let mut paths = Vec::new();
+        collect_files(&root, &mut paths)?;
+        let mut pending: Vec<(String, u64, Vec<LineWindow>)> = Vec::new();
+        for path in paths {
+            let relative = path.strip_prefix(&root).unwrap_or(&path).to_string_lossy().into_owned();
+            let mtime = mtime_millis(&path);
+            if let Some(existing) = previous.remove(&relative) {
+                if existing.mtime == mtime {
+                    stats.unchanged_files += 1;
+                    index.files.insert(relative, existing);
+                    continue;
+                }
+            }
+            // Binary and unreadable files are skipped; large ones are cut at the attachment limit
+            let Ok((text, _, _)) = attachments::read_text_file(&path, config.attachment_max_bytes) else {
+                debug!("Not indexing {:?}", path);
+                continue;
+            };
+            pending.push((relative, mtime, chunk_lines(&text, config.rag_chunk_lines, config.rag_chunk_overlap)));
+        }
+        stats.removed_files = previous.len();
+
+        // Embed all new chunks in batches, labeling each with its file for context
+        let texts: Vec<String> = pending
+            .iter()
+            .flat_map(|(path, _, chunks)| chunks.iter().map(move |(_, _, body)| format!("{}\n{}", path, body)))
+            .collect();
+        let mut vectors = Vec::with_capacity(texts.len());
+        let batch_size = config.embedding_batch_size.max(1);
+        for (i, batch) in texts.chunks(batch_size).enumerate() {
+            println!("Embedding chunks {}-{} of {}...", i * batch_size + 1, i * batch_size + batch.len(), texts.len());
+            vectors.extend(llm::embed(client, config, &index.provider, batch).await?);
+        }
+
+        let mut vectors = vectors.into_iter();
+        for (relative, mtime, chunks) in pending {
+            let chunks: Vec<Chunk> = chunks
+                .into_iter()
+                .zip(vectors.by_ref())
+                .map(|((start_line, end_line, text), vector)| Chunk { start_line, end_line, text, vector })
+                .collect();
+            stats.embedded_files += 1;
+            index.files.insert(relative, IndexedFile { mtime, chunks });
+        }
+        stats.chunks = index.chunk_count();
+        index.save()?;
+        Ok((index, stats))
+    }
+
+    // --- Retrieval ---
+    pub async fn search(&self, client: &Client, config: &Config, query: &str, top_k: usize) -> Result<Vec<Hit<'_>>> {
+        // The index's own model must embed the query for scores to mean anything
+        let mut query_config = config.clone();
+        match &self.provider {
+            LlmProvider::Ollama => query_config.ollama_embedding_model = self.model.clone(),
+            LlmProvider::Gemini => query_config.gemini_embedding_model = self.model.clone(),
+            LlmProvider::Groq => query_config.groq_embedding_model = Some(self.model.clone()),
+            LlmProvider::HuggingFace => query_config.huggingface_embedding_model = self.model.clone(),
+        }
+        let query_vector = llm::embed(client, &query_config, &self.provider, &[query.to_string()])
+            .await?
+            .pop()
+            .ok_or_else(|| anyhow!("No embedding returned for the question"))?;
+
+        let mut hits: Vec<Hit> = self
+            .files
+            .iter()
+            .flat_map(|(path, file)| file.chunks.iter().map(move |chunk| (path, chunk)))
+            .map(|(path, chunk)| Hit { path, chunk, score: cosine_similarity(&query_vector, &chunk.vector) })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(top_k);
+        Ok(hits)
+    }
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let denominator = norm(a) * norm(b);
+    if denominator == 0.0 { 0.0 } else { dot / denominator }
+}
+
+// Prepends the retrieved excerpts to the question, numbered for citation
+pub fn augment_prompt(question: &str, hits: &[Hit]) -> String {
+    let mut prompt = String::from(
+        "Answer using the numbered excerpts below where relevant, citing them as [n] with their source. \
+         Say so if they do not contain the answer.\n\n",
+    );
+    for (i, hit) in hits.iter().enumerate() {
+        let label = format!("[{}] {}:{}-{}", i + 1, hit.path, hit.chunk.start_line, hit.chunk.end_line);
+        let language = attachments::fence_language(Path::new(hit.path));
+        let _ = writeln!(prompt, "{}", attachments::fenced_block(&label, language, &hit.chunk.text));
+    }
+    let _ = write!(prompt, "Question: {}", question);
+    prompt
+}
@@ -0,0 +1,356 @@
+// src/shell.rs
+use crate::attachments;
+use crate::error::Result;
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::error;
+
+// --- Constants ---
+// Environment variables passed through to tool commands; everything else
+// (API keys in particular) is cleared.
+const RESTRICTED_ENV: [&str; 4] = ["PATH", "HOME", "LANG", "TERM"];
+
+// --- Interactive Commands ---
+
+// Runs a command through the platform shell, echoing its output to the
+// terminal. Returns the captured output, or `None` if it could not be started.
+pub fn run_shell_command(command_str: &str) -> Result<Option<Output>> {
+    println!("Executing: {}", command_str);
+    println!("---");
+    let output_result = if cfg!(target_os = "windows") {
+        Command::new("cmd").args(["/C", command_str]).output()
+    } else {
+        Command::new("sh").arg("-c").arg(command_str).output()
+    };
+    let captured = match output_result {
+        Ok(output) => {
+            if !output.stdout.is_empty() {
+                print!("{}", String::from_utf8_lossy(&output.stdout));
+            }
+            io::stdout().flush().context("Failed to flush stdout after command output")?;
+            if !output.stderr.is_empty() {
+                eprint!("{}", String::from_utf8_lossy(&output.stderr));
+            }
+            io::stderr().flush().context("Failed to flush stderr after command output")?;
+            if !output.status.success() {
+                eprintln!("\nCommand exited with status: {}", output.status);
+            }
+            Some(output)
+        }
+        Err(e) => {
+            error!("Failed to execute command '{}': {}", command_str, e);
+            eprintln!("Error executing command: {}", e);
+            None
+        }
+    };
+    println!("---");
+    Ok(captured)
+}
+
+// Formats a command's output as context for the model. Each stream is capped
+// at `max_bytes` so a noisy command cannot flood the context window.
+pub fn format_shell_output(command_str: &str, output: &Output, max_bytes: usize) -> String {
+    // Counts are of the raw output; invalid UTF-8 is only replaced for display
+    let cap = |bytes: &[u8]| {
+        if bytes.len() <= max_bytes {
+            return String::from_utf8_lossy(bytes).into_owned();
+        }
+        // Back up to the start of a UTF-8 sequence
+        let mut cut = max_bytes;
+        while cut > 0 && bytes[cut] & 0xC0 == 0x80 {
+            cut -= 1;
+        }
+        format!(
+            "{}\n[... truncated: showing the first {} of {} bytes ...]\n",
+            String::from_utf8_lossy(&bytes[..cut]),
+            cut,
+            bytes.len()
+        )
+    };
+    let mut context = format!("Output of shell command `{}` ({}):\n", command_str, output.status);
+    if output.stdout.is_empty() && output.stderr.is_empty() {
+        context.push_str("(no output)\n");
+    }
+    if !output.stdout.is_empty() {
+        context.push_str(&attachments::fenced_block("stdout:", "text", &cap(&output.stdout)));
+    }
+    if !output.stderr.is_empty() {
+        context.push_str(&attachments::fenced_block("stderr:", "text", &cap(&output.stderr)));
+    }
+    context
+}
+
+// --- Private Temporary Files ---
+// A file inside a fresh, randomly named directory that only the user can
+// access (0700 on Unix). Both are created exclusively, so a file or symlink
+// planted in the shared temp directory is never followed. The directory is
+// removed on drop.
+pub struct PrivateTempFile {
+    dir: PathBuf,
+    path: PathBuf,
+}
+
+impl PrivateTempFile {
+    pub fn create(file_name: &str, contents: &str) -> Result<PrivateTempFile> {
+        let dir = create_private_dir()?;
+        let path = dir.join(file_name);
+        // Owns the directory from here on, so it is removed if writing fails
+        let temp = PrivateTempFile { dir, path };
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp.path)
+            .context(format!("Failed to create {:?}", temp.path))?;
+        file.write_all(contents.as_bytes()).context(format!("Failed to write {:?}", temp.path))?;
+        Ok(temp)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PrivateTempFile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            error!("Failed to remove {:?}: {}", self.dir, e);
+        }
+    }
+}
+
+fn create_private_dir() -> Result<PathBuf> {
+    let mut builder = DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    for _ in 0..16 {
+        let dir = std::env::temp_dir().join(format!("llm-chat-{:016x}", random_u64()));
+        match builder.create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(anyhow!("Failed to create {:?}: {}", dir, e)),
+        }
+    }
+    Err(anyhow!("Failed to create a private directory in {:?}", std::env::temp_dir()))
+}
+
+// Unpredictable enough for a file name: std seeds every RandomState randomly
+fn random_u64() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_nanos()));
+    hasher.finish()
+}
+
+// --- Editor ---
+// Opens $VISUAL/$EDITOR (falling back to vi) on a temporary file seeded with
+// `initial` and returns what was saved, or `None` if it was left empty.
+pub fn compose_in_editor(initial: &str) -> Result<Option<String>> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| if cfg!(target_os = "windows") { "notepad" } else { "vi" }.to_string());
+    // Editors such as "code --wait" come with arguments
+    let mut words = editor.split_whitespace();
+    let program = words.next().ok_or_else(|| anyhow!("$EDITOR is empty"))?;
+
+    let seed = if initial.is_empty() { String::new() } else { format!("{}\n", initial) };
+    let file = PrivateTempFile::create("prompt.md", &seed)?;
+    let status = Command::new(program)
+        .args(words)
+        .arg(file.path())
+        .status()
+        .context(format!("Failed to start editor '{}'", editor))?;
+    let contents = fs::read_to_string(file.path()).context(format!("Failed to read {:?}", file.path()));
+    if !status.success() {
+        return Err(anyhow!("Editor '{}' exited with {}", editor, status));
+    }
+    let contents = contents?;
+    let prompt = contents.trim();
+    Ok((!prompt.is_empty()).then(|| prompt.to_string()))
+}
+
+// --- Sandboxed Commands ---
+// How tool commands are confined (`tool_sandbox` in config.json)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SandboxMode {
+    // The strongest sandbox that works here; refuse to run without one
+    #[default]
+    Auto,
+    // Require bubblewrap
+    Bwrap,
+    // Require unshare
+    Unshare,
+    // No confinement beyond the working directory, environment and time limit
+    Off,
+}
+
+impl fmt::Display for SandboxMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SandboxMode::Auto => "auto",
+            SandboxMode::Bwrap => "bwrap",
+            SandboxMode::Unshare => "unshare",
+            SandboxMode::Off => "off",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sandbox {
+    // Read-only view of the system, writable working directory, private /tmp,
+    // own process and network namespaces (no network)
+    Bwrap,
+    // Own user and network namespaces: no network, files as usual
+    Unshare,
+}
+
+impl Sandbox {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Sandbox::Bwrap => "bwrap (read-only system, writable working directory, no network)",
+            Sandbox::Unshare => "unshare (no network)",
+        }
+    }
+
+    // The wrapper command that runs `sh -c` inside this sandbox
+    fn wrapper(&self, dir: &Path) -> Vec<OsString> {
+        match self {
+            Sandbox::Bwrap => {
+                let mut args = ["bwrap", "--ro-bind", "/", "/", "--dev", "/dev", "--proc", "/proc", "--tmpfs", "/tmp"].map(OsString::from).to_vec();
+                // The working directory is the only writable place that persists
+                args.extend([OsStr::new("--bind"), dir.as_os_str(), dir.as_os_str(), OsStr::new("--chdir"), dir.as_os_str()].map(OsStr::to_os_string));
+                args.extend(["--unshare-all", "--die-with-parent", "--new-session", "--"].map(OsString::from));
+                args
+            }
+            Sandbox::Unshare => ["unshare", "--map-root-user", "--net", "--"].map(OsString::from).to_vec(),
+        }
+    }
+
+    // Whether the wrapper is installed and allowed to create namespaces here
+    fn works(&self) -> bool {
+        let dir = std::env::temp_dir();
+        let wrapper = self.wrapper(&dir);
+        Command::new(&wrapper[0])
+            .args(&wrapper[1..])
+            .arg("true")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    }
+}
+
+// The sandbox to run tool commands in, or None with `tool_sandbox` "off".
+// Probing starts a process, so the result is remembered.
+pub fn select_sandbox(mode: SandboxMode) -> Result<Option<Sandbox>> {
+    static WORKING: OnceLock<Vec<Sandbox>> = OnceLock::new();
+    let working = || {
+        WORKING.get_or_init(|| {
+            if cfg!(target_os = "linux") {
+                [Sandbox::Bwrap, Sandbox::Unshare].into_iter().filter(Sandbox::works).collect()
+            } else {
+                Vec::new()
+            }
+        })
+    };
+    let require = |sandbox: Sandbox| {
+        if working().contains(&sandbox) {
+            Ok(Some(sandbox))
+        } else {
+            Err(anyhow!("the '{}' sandbox is not available here", mode))
+        }
+    };
+    match mode {
+        SandboxMode::Off => Ok(None),
+        SandboxMode::Bwrap => require(Sandbox::Bwrap),
+        SandboxMode::Unshare => require(Sandbox::Unshare),
+        SandboxMode::Auto => working().first().copied().map(Some).ok_or_else(|| {
+            anyhow!("no sandbox is available (install bubblewrap, or set \"tool_sandbox\": \"off\" in config.json)")
+        }),
+    }
+}
+
+// Runs a command for a tool call: working directory fixed to `dir`, a minimal
+// environment, no stdin, inside `sandbox` if given, and killed after
+// `timeout`. Output is captured, not echoed.
+pub async fn run_restricted(command_str: &str, dir: &Path, timeout: Duration, sandbox: Option<Sandbox>) -> Result<Output> {
+    let mut command = if cfg!(target_os = "windows") {
+        let mut c = tokio::process::Command::new("cmd");
+        c.args(["/C", command_str]);
+        c
+    } else {
+        let wrapper = sandbox.map(|s| s.wrapper(dir)).unwrap_or_default();
+        let mut c = match wrapper.split_first() {
+            Some((program, args)) => {
+                let mut c = tokio::process::Command::new(program);
+                c.args(args).arg("sh");
+                c
+            }
+            None => tokio::process::Command::new("sh"),
+        };
+        c.arg("-c").arg(command_str);
+        c
+    };
+    command
+        .current_dir(dir)
+        .env_clear()
+        .envs(RESTRICTED_ENV.iter().filter_map(|k| std::env::var(k).ok().map(|v| (*k, v))))
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+
+    match tokio::time::timeout(timeout, command.output()).await {
+        Ok(result) => result.context(format!("Failed to execute command '{}'", command_str)),
+        Err(_) => Err(anyhow!("Command '{}' timed out after {}s", command_str, timeout.as_secs())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bwrap_only_binds_the_working_directory_writable() {
+        let wrapper = Sandbox::Bwrap.wrapper(Path::new("/work/project"));
+        let args: Vec<&str> = wrapper.iter().map(|a| a.to_str().unwrap()).collect();
+        let binds: Vec<&[&str]> = args.windows(3).filter(|w| w[0] == "--bind").collect();
+        assert_eq!(binds, [["--bind", "/work/project", "/work/project"]]);
+        assert!(args.windows(3).any(|w| w == ["--ro-bind", "/", "/"]));
+        assert!(args.contains(&"--unshare-all"));
+        assert_eq!(args.last(), Some(&"--"));
+    }
+
+    #[tokio::test]
+    async fn sandboxed_commands_get_their_own_network_namespace() {
+        // Namespaces may be unavailable (other platforms, restricted containers)
+        let Ok(sandbox) = select_sandbox(SandboxMode::Auto) else {
+            return;
+        };
+        let outside = fs::read_link("/proc/self/ns/net").unwrap();
+        let dir = std::env::temp_dir();
+        let output = run_restricted("readlink /proc/self/ns/net", &dir, Duration::from_secs(10), sandbox).await.unwrap();
+        assert!(output.status.success());
+        let inside = String::from_utf8_lossy(&output.stdout);
+        assert_ne!(inside.trim(), outside.to_string_lossy());
+    }
+
+    #[tokio::test]
+    async fn restricted_commands_time_out() {
+        let dir = std::env::temp_dir();
+        let error = run_restricted("sleep 5", &dir, Duration::from_secs(1), None).await.unwrap_err();
+        assert_eq!(error.to_string(), "Command 'sleep 5' timed out after 1s");
+    }
+}
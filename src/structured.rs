@@ -0,0 +1,75 @@
+// src/structured.rs
+use crate::error::Result;
+use anyhow::{anyhow, Context};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+// --- Constants ---
+// Validation errors quoted back to the model when re-prompting
+const MAX_REPORTED_ERRORS: usize = 10;
+
+// --- Response Schema ---
+// A JSON schema the answer must satisfy, compiled once for local validation.
+pub struct ResponseSchema {
+    pub schema: Value,
+    validator: jsonschema::Validator,
+}
+
+impl ResponseSchema {
+    pub fn new(schema: Value) -> Result<Self> {
+        let validator = jsonschema::validator_for(&schema).map_err(|e| anyhow!("Invalid JSON schema: {}", e))?;
+        Ok(ResponseSchema { schema, validator })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).context(format!("Failed to read schema {:?}", path))?;
+        let schema: Value = serde_json::from_str(&text).context(format!("Schema {:?} is not valid JSON", path))?;
+        ResponseSchema::new(schema)
+    }
+
+    // Parses and validates a reply. On failure returns the problems found,
+    // one per line, ready to be shown to the model.
+    pub fn validate(&self, reply: &str) -> std::result::Result<Value, Vec<String>> {
+        let value: Value = serde_json::from_str(strip_code_fence(reply))
+            .map_err(|e| vec![format!("The reply is not valid JSON: {}", e)])?;
+        let errors: Vec<String> = self
+            .validator
+            .iter_errors(&value)
+            .take(MAX_REPORTED_ERRORS)
+            .map(|e| {
+                let path = e.instance_path.to_string();
+                if path.is_empty() { e.to_string() } else { format!("at {}: {}", path, e) }
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(value)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl std::fmt::Debug for ResponseSchema {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResponseSchema").field("schema", &self.schema).finish()
+    }
+}
+
+// Models sometimes wrap JSON in a ```json fence even when asked not to
+fn strip_code_fence(reply: &str) -> &str {
+    let trimmed = reply.trim();
+    let Some(rest) = trimmed.strip_prefix("```") else {
+        return trimmed;
+    };
+    let body = rest.split_once('\n').map_or("", |(_, body)| body);
+    body.trim_end().strip_suffix("```").unwrap_or(body).trim()
+}
+
+// The follow-up message sent when a reply fails validation
+pub fn retry_prompt(errors: &[String]) -> String {
+    format!(
+        "Your reply did not match the required JSON schema:\n- {}\nReply again with only the corrected JSON.",
+        errors.join("\n- ")
+    )
+}
@@ -0,0 +1,103 @@
+// src/summary.rs
+use crate::config::{Config, LlmProvider};
+use crate::conversation::Conversation;
+use crate::error::Result;
+use crate::llm::message::{Message, Role};
+use crate::llm::{self, Completion, Generation, RequestOptions};
+use anyhow::{anyhow, Context};
+use reqwest::Client;
+use std::fmt::Write as _;
+use tracing::debug;
+
+// --- Constants ---
+const SUMMARY_PREFIX: &str = "Summary of the earlier conversation:\n";
+// Most recent messages always kept verbatim (the last two exchanges)
+const KEEP_RECENT_MESSAGES: usize = 4;
+const SUMMARY_INSTRUCTIONS: &str = "Summarize the following conversation so it can replace the \
+original messages as context for continuing it. Keep facts, decisions, names, file paths, code \
+identifiers and open questions. Be concise and write in the third person. Reply with the summary only.";
+
+// --- Compaction Result ---
+pub struct Compaction {
+    pub summarized_messages: usize,
+    // The summarization request itself, for usage and cost accounting
+    pub generation: Generation,
+}
+
+fn is_summary(message: &Message) -> bool {
+    message.role == Role::System && message.content.starts_with(SUMMARY_PREFIX)
+}
+
+// The config used for summary requests: the designated summary provider/model
+// if configured, the active one otherwise.
+fn summary_config(config: &Config) -> Config {
+    let mut summary_config = config.clone();
+    if let Some(provider) = &config.summary_provider {
+        summary_config.active_provider = provider.clone();
+    }
+    if let Some(model) = &config.summary_model {
+        let provider = summary_config.active_provider.clone();
+        summary_config.set_provider_model(&provider, model.clone());
+    }
+    summary_config
+}
+
+// --- Compaction ---
+// Summarizes everything but the most recent messages and replaces it with a
+// single system message. Earlier summaries are folded into the new one.
+// Returns `None` when there is nothing old enough to summarize (or only an
+// earlier summary). Fails without a request if the summary provider is in
+// `blocked` (over the monthly hard budget).
+pub async fn compact(
+    client: &Client,
+    config: &Config,
+    conversation: &mut Conversation,
+    blocked: &[LlmProvider],
+) -> Result<Option<Compaction>> {
+    let messages = conversation.messages();
+    let mut split = messages.len().saturating_sub(KEEP_RECENT_MESSAGES);
+    // Start the kept tail on a user message so no reply loses its prompt
+    while split > 0 && messages[split].role != Role::User {
+        split -= 1;
+    }
+
+    let (old, recent) = messages.split_at(split);
+    let to_summarize: Vec<&Message> = old.iter().filter(|m| m.role != Role::System || is_summary(m)).collect();
+    if to_summarize.iter().all(|m| is_summary(m)) {
+        return Ok(None);
+    }
+
+    let mut transcript = String::new();
+    for message in &to_summarize {
+        let _ = writeln!(transcript, "{}: {}\n", message.role, message.content);
+    }
+    let request = [Message::user(format!("{}\n\n{}", SUMMARY_INSTRUCTIONS, transcript))];
+
+    let summary_config = summary_config(config);
+    let provider = summary_config.active_provider.clone();
+    if blocked.contains(&provider) {
+        return Err(anyhow!("{} is a paid provider and the monthly hard budget is exceeded", provider));
+    }
+    debug!("Summarizing {} messages via {}", to_summarize.len(), provider);
+    let Completion { text, usage, .. } = llm::generate(client, &summary_config, &provider, &request, &RequestOptions::default())
+        .await
+        .context("Failed to summarize the conversation")?;
+
+    // Keep non-summary system prompts, then the new summary, then the recent tail
+    let mut compacted: Vec<Message> = old.iter().filter(|m| m.role == Role::System && !is_summary(m)).cloned().collect();
+    compacted.push(Message::system(format!("{}{}", SUMMARY_PREFIX, text.trim())));
+    compacted.extend(recent.iter().cloned());
+    let summarized_messages = to_summarize.len();
+    conversation.rewrite(|messages| *messages = compacted);
+
+    Ok(Some(Compaction {
+        summarized_messages,
+        generation: Generation {
+            text,
+            usage,
+            model: summary_config.get_active_model_name().to_string(),
+            provider,
+            tool_calls: Vec::new(),
+        },
+    }))
+}
@@ -0,0 +1,235 @@
+// src/templates.rs
+use crate::config::{self, LlmProvider};
+use crate::error::Result;
+use anyhow::{anyhow, Context};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+// --- Constants ---
+const TEMPLATES_DIR: &str = "templates";
+const TEMPLATE_EXTENSION: &str = "md";
+
+// Written to an empty templates directory as a starting point
+const STARTER_TEMPLATES: [(&str, &str); 3] = [
+    (
+        "review",
+        "---\n\
+         description: Review code for bugs, clarity and style\n\
+         temperature: 0.2\n\
+         ---\n\
+         Review the following {{lang=code}} for bugs, unclear naming and style problems.\n\
+         List concrete issues with suggested fixes, most important first.\n\n\
+         {{file}}\n",
+    ),
+    (
+        "explain-error",
+        "---\n\
+         description: Explain a compiler or runtime error and how to fix it\n\
+         ---\n\
+         Explain this error: what it means, the likely cause, and how to fix it.\n\n\
+         {{error}}\n",
+    ),
+    (
+        "write-tests",
+        "---\n\
+         description: Write unit tests for the given code\n\
+         ---\n\
+         Write {{framework=idiomatic}} unit tests for the following {{lang=code}}.\n\
+         Cover edge cases and error paths. Reply with the test code only.\n\n\
+         {{file}}\n",
+    ),
+];
+
+// --- Templates ---
+// A prompt file under <config dir>/templates/<name>.md with `{{var}}` or
+// `{{var=default}}` placeholders and an optional front matter block:
+//
+//   ---
+//   description: Review code
+//   provider: groq
+//   model: llama-3.3-70b-versatile
+//   temperature: 0.2
+//   ---
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub name: String,
+    pub description: Option<String>,
+    pub provider: Option<LlmProvider>,
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    body: String,
+}
+
+// A placeholder in order of first appearance
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub name: String,
+    pub default: Option<String>,
+}
+
+pub fn templates_dir() -> PathBuf {
+    config::config_dir().join(TEMPLATES_DIR)
+}
+
+// Creates the directory with the starter templates on first use
+fn ensure_templates_dir() -> Result<PathBuf> {
+    let dir = templates_dir();
+    if !dir.exists() {
+        fs::create_dir_all(&dir).context(format!("Failed to create {:?}", dir))?;
+        for (name, text) in STARTER_TEMPLATES {
+            let path = dir.join(format!("{}.{}", name, TEMPLATE_EXTENSION));
+            fs::write(&path, text).context(format!("Failed to write {:?}", path))?;
+        }
+    }
+    Ok(dir)
+}
+
+// Template names, sorted; used for listing and tab completion
+pub fn list_names() -> Vec<String> {
+    let Ok(dir) = ensure_templates_dir() else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|e| e == TEMPLATE_EXTENSION))
+                .filter_map(|path| path.file_stem().map(|s| s.to_string_lossy().into_owned()))
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+impl Template {
+    pub fn load(name: &str) -> Result<Template> {
+        let path = ensure_templates_dir()?.join(format!("{}.{}", name, TEMPLATE_EXTENSION));
+        let text = fs::read_to_string(&path).map_err(|_| anyhow!("No template '{}' in {:?}", name, templates_dir()))?;
+        Template::parse(name, &text).context(format!("Invalid template {:?}", path))
+    }
+
+    fn parse(name: &str, text: &str) -> Result<Template> {
+        let mut template = Template {
+            name: name.to_string(),
+            description: None,
+            provider: None,
+            model: None,
+            temperature: None,
+            body: text.to_string(),
+        };
+        let Some(rest) = text.strip_prefix("---\n") else {
+            return Ok(template);
+        };
+        let (header, body) = rest
+            .split_once("\n---\n")
+            .or_else(|| rest.strip_suffix("\n---").map(|header| (header, "")))
+            .ok_or_else(|| anyhow!("Front matter is not closed with '---'"))?;
+        for line in header.lines().filter(|l| !l.trim().is_empty()) {
+            let (key, value) = line.split_once(':').ok_or_else(|| anyhow!("Expected 'key: value', got '{}'", line))?;
+            let value = value.trim();
+            match key.trim() {
+                "description" => template.description = Some(value.to_string()),
+                "provider" => {
+                    template.provider = Some(
+                        LlmProvider::from_config_name(value).ok_or_else(|| anyhow!("Unknown provider '{}'", value))?,
+                    )
+                }
+                "model" => template.model = Some(value.to_string()),
+                "temperature" => {
+                    template.temperature =
+                        Some(value.parse().map_err(|_| anyhow!("Invalid temperature '{}'", value))?)
+                }
+                other => return Err(anyhow!("Unknown front matter key '{}'", other)),
+            }
+        }
+        template.body = body.to_string();
+        Ok(template)
+    }
+
+    pub fn variables(&self) -> Vec<Variable> {
+        let mut variables: Vec<Variable> = Vec::new();
+        for (name, default) in placeholders(&self.body) {
+            if !variables.iter().any(|v| v.name == name) {
+                variables.push(Variable { name: name.to_string(), default: default.map(String::from) });
+            }
+        }
+        variables
+    }
+
+    // Substitutes every placeholder; unknown ones fall back to their default
+    // (or are left as written).
+    pub fn render(&self, values: &HashMap<String, String>) -> String {
+        let mut rendered = String::with_capacity(self.body.len());
+        let mut rest = self.body.as_str();
+        while let Some(start) = rest.find("{{") {
+            let Some(len) = rest[start..].find("}}") else {
+                break;
+            };
+            let inner = &rest[start + 2..start + len];
+            let (name, default) = split_placeholder(inner);
+            rendered.push_str(&rest[..start]);
+            match values.get(name).map(String::as_str).or(default) {
+                Some(value) => rendered.push_str(value),
+                None => rendered.push_str(&rest[start..start + len + 2]),
+            }
+            rest = &rest[start + len + 2..];
+        }
+        rendered.push_str(rest);
+        rendered.trim().to_string()
+    }
+}
+
+fn split_placeholder(inner: &str) -> (&str, Option<&str>) {
+    match inner.split_once('=') {
+        Some((name, default)) => (name.trim(), Some(default.trim())),
+        None => (inner.trim(), None),
+    }
+}
+
+fn placeholders(body: &str) -> Vec<(&str, Option<&str>)> {
+    let mut found = Vec::new();
+    let mut rest = body;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+        found.push(split_placeholder(&rest[start + 2..start + len]));
+        rest = &rest[start + len + 2..];
+    }
+    found
+}
+
+// --- Arguments ---
+// Parses `key=value` pairs; double quotes keep spaces inside a value.
+pub fn parse_assignments(args: &str) -> Result<HashMap<String, String>> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in args.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if quoted {
+        return Err(anyhow!("Unclosed quote in '{}'", args));
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+        .into_iter()
+        .map(|token| {
+            let (key, value) = token.split_once('=').ok_or_else(|| anyhow!("Expected key=value, got '{}'", token))?;
+            Ok((key.to_string(), value.to_string()))
+        })
+        .collect()
+}
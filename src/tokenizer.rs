@@ -0,0 +1,78 @@
+// src/tokenizer.rs
+use crate::llm::message::Message;
+use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer as BpeKind};
+use tiktoken_rs::CoreBPE;
+
+// --- Constants ---
+// Rough overhead of the chat template around each message / the whole request
+const TOKENS_PER_MESSAGE: usize = 4;
+const TOKENS_PER_REQUEST: usize = 3;
+// Rough flat cost of one image (providers charge ~250-800 tokens each)
+const TOKENS_PER_IMAGE: usize = 765;
+// Heuristic used when no exact tokenizer is known (~4 characters per token)
+const CHARS_PER_TOKEN: usize = 4;
+
+// --- Tokenizer ---
+// Exact counts for models using a known BPE vocabulary, a character-based
+// estimate for everything else (Llama, Gemini, ...).
+pub enum Tokenizer {
+    Bpe { name: &'static str, bpe: &'static CoreBPE },
+    Heuristic,
+}
+
+impl Tokenizer {
+    pub fn for_model(model: &str) -> Self {
+        // Strip an org prefix such as "openai/gpt-oss-20b"
+        let base_name = model.rsplit('/').next().unwrap_or(model);
+        match get_tokenizer(base_name) {
+            Some(kind) => match tiktoken_rs::bpe_for_tokenizer(kind) {
+                Ok(bpe) => Tokenizer::Bpe { name: bpe_name(kind), bpe },
+                Err(_) => Tokenizer::Heuristic,
+            },
+            None => Tokenizer::Heuristic,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Tokenizer::Bpe { name, .. } => name,
+            Tokenizer::Heuristic => "heuristic",
+        }
+    }
+
+    pub fn is_exact(&self) -> bool {
+        matches!(self, Tokenizer::Bpe { .. })
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        match self {
+            Tokenizer::Bpe { bpe, .. } => bpe.encode_with_special_tokens(text).len(),
+            Tokenizer::Heuristic => text.chars().count().div_ceil(CHARS_PER_TOKEN),
+        }
+    }
+
+    pub fn count_message(&self, message: &Message) -> usize {
+        let tool_calls: usize = message
+            .tool_calls
+            .iter()
+            .map(|call| self.count(&call.name) + self.count(&call.arguments.to_string()))
+            .sum();
+        self.count(&message.content) + tool_calls + message.images.len() * TOKENS_PER_IMAGE + TOKENS_PER_MESSAGE
+    }
+
+    pub fn count_messages(&self, messages: &[Message]) -> usize {
+        messages.iter().map(|m| self.count_message(m)).sum::<usize>() + TOKENS_PER_REQUEST
+    }
+}
+
+fn bpe_name(kind: BpeKind) -> &'static str {
+    match kind {
+        BpeKind::O200kHarmony => "o200k_harmony",
+        BpeKind::O200kBase => "o200k_base",
+        BpeKind::Cl100kBase => "cl100k_base",
+        BpeKind::P50kBase => "p50k_base",
+        BpeKind::R50kBase => "r50k_base",
+        BpeKind::P50kEdit => "p50k_edit",
+        BpeKind::Gpt2 => "gpt2",
+    }
+}
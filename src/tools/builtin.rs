@@ -0,0 +1,150 @@
+// src/tools/builtin.rs
+use crate::agent::{self, Approval};
+use crate::attachments;
+use crate::config::Config;
+use crate::error::Result;
+use crate::shell;
+use crate::tools::ToolSpec;
+use anyhow::{anyhow, Context};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// --- Constants ---
+// Entries listed by list_dir before the listing is cut off
+const MAX_DIR_ENTRIES: usize = 500;
+
+// --- Built-in Tools ---
+// File tools are confined to the current working directory; run_shell runs
+// there too, in a sandbox and after the same approval as agent mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    ReadFile,
+    ListDir,
+    RunShell,
+}
+
+impl Builtin {
+    pub const ALL: [Builtin; 3] = [Builtin::ReadFile, Builtin::ListDir, Builtin::RunShell];
+
+    pub fn spec(&self) -> ToolSpec {
+        let (name, description, parameters) = match self {
+            Builtin::ReadFile => (
+                "read_file",
+                "Read a text file inside the current working directory.",
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "path": { "type": "string", "description": "Path relative to the working directory" }
+                    },
+                    "required": ["path"]
+                }),
+            ),
+            Builtin::ListDir => (
+                "list_dir",
+                "List the entries of a directory inside the current working directory. Directories end with '/'.",
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "path": { "type": "string", "description": "Path relative to the working directory; defaults to '.'" }
+                    }
+                }),
+            ),
+            Builtin::RunShell => (
+                "run_shell",
+                "Run a shell command in the current working directory and return its output. Commands run in a sandbox without network access and with a time limit; files outside the working directory may be read-only. The user must approve each command.",
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "command": { "type": "string", "description": "The shell command to run" }
+                    },
+                    "required": ["command"]
+                }),
+            ),
+        };
+        ToolSpec { name: name.to_string(), description: description.to_string(), parameters }
+    }
+
+    pub async fn execute(&self, config: &Config, arguments: &Value) -> Result<String> {
+        match self {
+            Builtin::ReadFile => read_file(config, arguments),
+            Builtin::ListDir => list_dir(arguments),
+            Builtin::RunShell => run_shell(config, arguments).await,
+        }
+    }
+}
+
+fn string_argument<'a>(arguments: &'a Value, name: &str) -> Option<&'a str> {
+    arguments.get(name).and_then(Value::as_str)
+}
+
+// Resolves `path` against the working directory and rejects anything that
+// ends up outside it (including via `..` or symlinks)
+fn confined_path(path: &str) -> Result<PathBuf> {
+    let root = std::env::current_dir()
+        .and_then(|dir| dir.canonicalize())
+        .context("Failed to resolve the working directory")?;
+    let resolved = root
+        .join(path)
+        .canonicalize()
+        .context(format!("'{}' does not exist", path))?;
+    if !resolved.starts_with(&root) {
+        return Err(anyhow!("'{}' is outside the working directory", path));
+    }
+    Ok(resolved)
+}
+
+fn read_file(config: &Config, arguments: &Value) -> Result<String> {
+    let path = string_argument(arguments, "path").ok_or_else(|| anyhow!("missing 'path' argument"))?;
+    let resolved = confined_path(path)?;
+    let (mut text, size, truncated) = attachments::read_text_file(&resolved, config.attachment_max_bytes)?;
+    if truncated {
+        let shown = text.len();
+        text.push_str(&format!(
+            "\n[... truncated: showing the first {} of {} bytes ...]\n",
+            shown, size
+        ));
+    }
+    Ok(text)
+}
+
+fn list_dir(arguments: &Value) -> Result<String> {
+    let path = string_argument(arguments, "path").unwrap_or(".");
+    let resolved = confined_path(path)?;
+    let mut entries: Vec<String> = fs::read_dir(&resolved)
+        .context(format!("Failed to read directory '{}'", path))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if entry.path().is_dir() { format!("{}/", name) } else { name }
+        })
+        .collect();
+    entries.sort();
+    let total = entries.len();
+    entries.truncate(MAX_DIR_ENTRIES);
+    let mut listing = entries.join("\n");
+    if total > MAX_DIR_ENTRIES {
+        listing.push_str(&format!("\n[... {} more entries ...]", total - MAX_DIR_ENTRIES));
+    }
+    if listing.is_empty() {
+        listing.push_str("(empty directory)");
+    }
+    Ok(listing)
+}
+
+async fn run_shell(config: &Config, arguments: &Value) -> Result<String> {
+    let requested = string_argument(arguments, "command").ok_or_else(|| anyhow!("missing 'command' argument"))?;
+    let sandbox = shell::select_sandbox(config.tool_sandbox).context("run_shell is unavailable")?;
+    match sandbox {
+        Some(sandbox) => println!("Sandbox: {}", sandbox.description()),
+        None => println!("Sandbox: off (the command runs with your permissions)"),
+    }
+    let command = match agent::approve_command(config, requested)? {
+        Approval::Run(command) => command,
+        Approval::Refused(message) => return Ok(message),
+    };
+    let dir: &Path = &std::env::current_dir().context("Failed to resolve the working directory")?;
+    let output = shell::run_restricted(&command, dir, Duration::from_secs(config.tool_timeout_secs), sandbox).await?;
+    Ok(shell::format_shell_output(&command, &output, config.attachment_max_bytes))
+}
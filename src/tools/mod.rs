@@ -0,0 +1,135 @@
+// src/tools/mod.rs
+pub mod builtin;
+
+use crate::config::Config;
+use crate::error::Result;
+use crate::llm::message::ToolCall;
+use crate::mcp::McpServer;
+use anyhow::Context;
+use serde::Serialize;
+use std::io::{self, Write};
+use tracing::debug;
+
+// --- Constants ---
+// Longest function name providers accept
+const MAX_FUNCTION_NAME: usize = 64;
+
+// --- Tool Description ---
+// What the model sees: a name, a description and a JSON schema for the arguments.
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolSpec {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+}
+
+// --- Tool Registry ---
+// How a registered tool is run
+#[derive(Debug, Clone)]
+enum Executor {
+    Builtin(builtin::Builtin),
+    // A tool of a running MCP server, by its name on that server
+    Mcp { server: McpServer, tool: String },
+}
+
+#[derive(Debug, Clone)]
+struct RegisteredTool {
+    spec: ToolSpec,
+    executor: Executor,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ToolRegistry {
+    tools: Vec<RegisteredTool>,
+}
+
+impl ToolRegistry {
+    // Registry with the built-in tools
+    pub fn with_builtins() -> Self {
+        let mut registry = ToolRegistry::default();
+        for tool in builtin::Builtin::ALL {
+            registry.tools.push(RegisteredTool { spec: tool.spec(), executor: Executor::Builtin(tool) });
+        }
+        registry
+    }
+
+    // Adds the tools of an MCP server as "<server>__<tool>"
+    pub fn register_mcp(&mut self, server: &McpServer) {
+        for tool in &server.tools {
+            let parameters = if tool.input_schema.is_object() {
+                tool.input_schema.clone()
+            } else {
+                serde_json::json!({ "type": "object", "properties": {} })
+            };
+            let spec = ToolSpec {
+                name: self.unique_name(&function_name(&format!("{}__{}", server.name, tool.name))),
+                description: tool.description.clone().unwrap_or_else(|| format!("{} tool from MCP server {}", tool.name, server.name)),
+                parameters,
+            };
+            self.tools.push(RegisteredTool {
+                spec,
+                executor: Executor::Mcp { server: server.clone(), tool: tool.name.clone() },
+            });
+        }
+    }
+
+    // Sanitizing and truncating can map two tools to one name; number the
+    // later ones ("name_2") so each call still reaches the right tool
+    fn unique_name(&self, name: &str) -> String {
+        let taken = |candidate: &str| self.tools.iter().any(|t| t.spec.name == candidate);
+        if !taken(name) {
+            return name.to_string();
+        }
+        (2..)
+            .map(|n| {
+                let suffix = format!("_{}", n);
+                let base: String = name.chars().take(MAX_FUNCTION_NAME - suffix.len()).collect();
+                base + &suffix
+            })
+            .find(|candidate| !taken(candidate))
+            .expect("unbounded range")
+    }
+
+    pub fn specs(&self) -> Vec<ToolSpec> {
+        self.tools.iter().map(|t| t.spec.clone()).collect()
+    }
+
+    // Runs a tool call. Failures are returned as text so the model can react
+    // to them like any other result.
+    pub async fn execute(&self, config: &Config, call: &ToolCall) -> String {
+        let Some(tool) = self.tools.iter().find(|t| t.spec.name == call.name) else {
+            return format!("Error: unknown tool '{}'.", call.name);
+        };
+        debug!("Executing tool {} with {}", call.name, call.arguments);
+        let result = match &tool.executor {
+            Executor::Builtin(builtin) => builtin.execute(config, &call.arguments).await,
+            Executor::Mcp { server, tool } => match approve_mcp_call(server) {
+                Ok(true) => server.call_tool(tool, &call.arguments).await,
+                Ok(false) => Ok(format!("The user declined to run tool '{}'.", call.name)),
+                Err(e) => Err(e),
+            },
+        };
+        result.unwrap_or_else(|e| format!("Error: {:#}", e))
+    }
+}
+
+// MCP servers run arbitrary code, so each call needs a yes unless the server
+// is trusted in config.json
+fn approve_mcp_call(server: &McpServer) -> Result<bool> {
+    if server.trusted {
+        return Ok(true);
+    }
+    print!("Allow MCP server '{}' to run it? [y/N]: ", server.name);
+    io::stdout().flush().context("Flush failed")?;
+    let mut buf = String::new();
+    io::stdin().read_line(&mut buf).context("Read failed")?;
+    Ok(matches!(buf.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+// Providers only accept [a-zA-Z0-9_-]{1,64} as function names
+fn function_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .take(MAX_FUNCTION_NAME)
+        .collect()
+}